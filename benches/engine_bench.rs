@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use lobx_rs::engine::book::Book;
+use lobx_rs::engine::types::{OrderRequest, Side};
+
+// A repeatable mix of resting limits on both sides and crossing orders
+fn order_flow(n: usize) -> Vec<OrderRequest> {
+    (0..n)
+        .map(|i| {
+            let side = if i % 2 == 0 { Side::BUY } else { Side::SELL };
+            let offset = (i % 7) as i64;
+            match i % 5 {
+                // Aggressive: market order sweeping the other side
+                4 => OrderRequest { side, price: None, quantity: 3, client_id: None },
+                _ => {
+                    let price = match side {
+                        Side::BUY => 995 + offset,
+                        Side::SELL => 1_002 - offset,
+                    };
+                    OrderRequest { side, price: Some(price), quantity: 10, client_id: None }
+                }
+            }
+        })
+        .collect()
+}
+
+fn bench_submit(c: &mut Criterion) {
+    let flow = order_flow(10_000);
+    // Run with `--features hot-path` and compare to measure instrumentation overhead
+    c.bench_function("submit_10k_mixed", |b| {
+        b.iter_batched(
+            Book::new,
+            |mut book| {
+                for req in &flow {
+                    black_box(book.submit(req));
+                }
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_submit);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::engine::book::Book;
+use crate::engine::types::{OrderId, OrderRequest, Side, SubmitResult};
+
+// Shared async handle to a Book. Callers clone this instead of passing around
+// Arc<Mutex<Book>> and locking by hand. Every call waits for the lock, so an
+// operation is never dropped because another task happens to hold it.
+#[derive(Debug, Clone)]
+pub struct AsyncBook {
+    inner: Arc<Mutex<Book>>,
+}
+
+impl AsyncBook {
+    pub fn new(book: Book) -> Self {
+        AsyncBook { inner: Arc::new(Mutex::new(book)) }
+    }
+
+    pub async fn submit(&self, req: &OrderRequest) -> (OrderId, SubmitResult) {
+        self.inner.lock().await.submit(req)
+    }
+
+    pub async fn cancel(&self, id: OrderId) -> Option<SubmitResult> {
+        let mut book = self.inner.lock().await;
+        let ts = book.now();
+        book.cancel(id, ts)
+    }
+
+    // Up to `levels` aggregated (price, size) levels per side, best first:
+    // (bids, asks).
+    pub async fn depth(&self, levels: usize) -> (Vec<(i64, u64)>, Vec<(i64, u64)>) {
+        let book = self.inner.lock().await;
+        (book.depth(Side::BUY, levels), book.depth(Side::SELL, levels))
+    }
+
+    // Run a read-only query against the book under the lock.
+    pub async fn read<R>(&self, f: impl FnOnce(&Book) -> R) -> R {
+        f(&*self.inner.lock().await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_no_operations_dropped_under_contention() {
+        let book = AsyncBook::new(Book::new());
+
+        // Hold the lock while the writers start so every one of them has to wait
+        let guard = book.inner.lock().await;
+        let mut handles = Vec::new();
+        for i in 0..8u64 {
+            let book = book.clone();
+            handles.push(tokio::spawn(async move {
+                let mut ids = Vec::new();
+                for j in 0..50 {
+                    let req = OrderRequest { side: Side::BUY, price: Some(100 + (i as i64)), quantity: 1 + j, client_id: None };
+                    ids.push(book.submit(&req).await.0);
+                }
+                // Cancel every other order
+                for id in ids.iter().step_by(2) {
+                    assert!(book.cancel(*id).await.is_some());
+                }
+            }));
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        drop(guard);
+        for h in handles {
+            h.await.unwrap();
+        }
+
+        let (total_orders, next_id) = book.read(|b| (b.stats().total_orders, b.next_order_id())).await;
+        assert_eq!(next_id, 1 + 8 * 50);
+        assert_eq!(total_orders, 8 * 25);
+        let (bids, asks) = book.depth(3).await;
+        assert_eq!(bids.iter().map(|(p, _)| *p).collect::<Vec<_>>(), vec![107, 106, 105]);
+        assert!(asks.is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::types::{DoneReason, Event, OrderId, OrderRequest};
+
+// Order lifecycle transitions recorded for the audit trail. `Executed` is one
+// record per fill on each side; `Filled` marks the order completing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditAction {
+    Received,
+    Rested,
+    Executed,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+// One append-only audit line. `ts_ms` is the book's timestamp for the transition,
+// the same one its events carry (epoch millis, or a sim clock's units). `qty` is
+// the order size for Received, the fill size for Executed and the open size for
+// the other transitions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub ts_ms: u64,
+    pub order_id: OrderId,
+    pub client_id: Option<u64>,
+    pub action: AuditAction,
+    pub price: Option<i64>,
+    pub qty: u64,
+    // The other side of an Executed record
+    pub counterparty_id: Option<OrderId>,
+}
+
+impl AuditRecord {
+    pub fn received(order_id: OrderId, req: &OrderRequest, ts: u64) -> Self {
+        AuditRecord {
+            ts_ms: ts,
+            order_id,
+            client_id: req.client_id,
+            action: AuditAction::Received,
+            price: req.price,
+            qty: req.quantity,
+            counterparty_id: None,
+        }
+    }
+
+    // The records an engine event stands for: two for a fill (taker then maker),
+    // one for a Done. An external fill only has our maker's side to record.
+    pub fn from_event(event: &Event) -> Vec<AuditRecord> {
+        let ts_ms = event.ts();
+        match event {
+            Event::Ack { .. } => vec![],
+            Event::Fill { taker_id, maker_id, taker_client_id, maker_client_id, price, qty, .. } => {
+                let maker = AuditRecord { ts_ms, order_id: *maker_id, client_id: *maker_client_id, action: AuditAction::Executed, price: Some(*price), qty: *qty, counterparty_id: Some(*taker_id) };
+                if *taker_id == OrderId::EXTERNAL {
+                    return vec![maker];
+                }
+                vec![
+                    AuditRecord { ts_ms, order_id: *taker_id, client_id: *taker_client_id, action: AuditAction::Executed, price: Some(*price), qty: *qty, counterparty_id: Some(*maker_id) },
+                    maker,
+                ]
+            }
+            Event::Done { id, client_id, reason, remaining, .. } => {
+                let action = match reason {
+                    DoneReason::Filled => AuditAction::Filled,
+                    DoneReason::Rested => AuditAction::Rested,
+                    DoneReason::Cancelled | DoneReason::PartiallyFilledThenCancelled => AuditAction::Cancelled,
+                    DoneReason::Rejected(_) => AuditAction::Rejected,
+                };
+                vec![AuditRecord { ts_ms, order_id: *id, client_id: *client_id, action, price: None, qty: *remaining, counterparty_id: None }]
+            }
+        }
+    }
+}
+
+// Destination for audit records. Separate from tracing: this is the compliance
+// trail, so every record is written and nothing is sampled or filtered by level.
+pub trait AuditSink: Send {
+    fn record(&mut self, record: &AuditRecord) -> std::io::Result<()>;
+}
+
+impl std::fmt::Debug for dyn AuditSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuditSink")
+    }
+}
+
+// Appends one JSON object per line to a file, flushing after each record so a
+// crash loses at most the record being written.
+pub struct NdjsonFileSink {
+    writer: BufWriter<File>,
+}
+
+impl NdjsonFileSink {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(NdjsonFileSink { writer: BufWriter::new(file) })
+    }
+}
+
+impl AuditSink for NdjsonFileSink {
+    fn record(&mut self, record: &AuditRecord) -> std::io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::engine::book::Book;
+    use crate::engine::clock::SimClock;
+    use crate::engine::types::Side;
+
+    struct MemorySink(Arc<Mutex<Vec<AuditRecord>>>);
+
+    impl AuditSink for MemorySink {
+        fn record(&mut self, record: &AuditRecord) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_ndjson_sink_appends_submit_then_fill() {
+        let path = std::env::temp_dir().join(format!("lobx-audit-{}.ndjson", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut book = Book::new();
+        book.set_audit_sink(Box::new(NdjsonFileSink::open(&path).unwrap()));
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: Some(3) });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 5, client_id: None });
+        drop(book);
+
+        let records: Vec<AuditRecord> = std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        let summary: Vec<_> = records.iter().map(|r| (r.order_id.0, r.action, r.qty, r.counterparty_id.map(u64::from))).collect();
+        assert_eq!(summary, vec![
+            (1, AuditAction::Received, 5, None),
+            (1, AuditAction::Rested, 5, None),
+            (2, AuditAction::Received, 5, None),
+            (2, AuditAction::Executed, 5, Some(1)),
+            (1, AuditAction::Executed, 5, Some(2)),
+            (1, AuditAction::Filled, 0, None),
+            (2, AuditAction::Filled, 0, None),
+        ]);
+        assert_eq!(records[0].client_id, Some(3));
+        assert_eq!(records[3].price, Some(10));
+        assert!(records.windows(2).all(|w| w[0].ts_ms <= w[1].ts_ms));
+    }
+
+    #[test]
+    fn test_external_fill_records_only_our_maker() {
+        let event = Event::Fill { taker_id: OrderId::EXTERNAL, maker_id: OrderId(4), taker_client_id: None, maker_client_id: Some(9), price: 100, qty: 3, ts: 7 };
+        let records = AuditRecord::from_event(&event);
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].order_id, records[0].client_id, records[0].counterparty_id), (OrderId(4), Some(9), Some(OrderId::EXTERNAL)));
+    }
+
+    #[test]
+    fn test_records_carry_the_books_clock() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let mut book = Book::new_for_sim(Box::new(SimClock::new(1000, 5)));
+        book.set_audit_sink(Box::new(MemorySink(records.clone())));
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 2, client_id: None });
+        book.submit_batch(&[OrderRequest {side: Side::BUY, price: Some(9), quantity: 1, client_id: None }], true);
+
+        let stamps: Vec<_> = records.lock().unwrap().iter().map(|r| (r.order_id.0, r.action, r.ts_ms)).collect();
+        assert_eq!(stamps, vec![
+            (1, AuditAction::Received, 1000),
+            (1, AuditAction::Rested, 1000),
+            (2, AuditAction::Received, 1005),
+            (2, AuditAction::Executed, 1005),
+            (1, AuditAction::Executed, 1005),
+            (2, AuditAction::Filled, 1005),
+            (3, AuditAction::Received, 1010),
+            (3, AuditAction::Rested, 1010),
+        ]);
+    }
+}
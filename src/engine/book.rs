@@ -1,86 +1,378 @@
 use std::collections::BTreeMap;
-use std::collections::VecDeque;
 use std::collections::HashMap;
 
-use crate::engine::types::{DoneReason, Order, OrderRequest, SubmitResult, Resting, Side, Event, BookError};
-use std::time::Instant;
-use tracing::{info, debug, warn, trace, error, instrument};
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::engine::audit::{AuditRecord, AuditSink};
+use crate::engine::clock::{now_ms, Clock};
+use crate::engine::ids::IdAllocator;
+use crate::engine::matcher::{coalesce_fills, exhausted_makers, match_order};
+use crate::engine::observer::EventObserver;
+use crate::engine::types::{AggregateOverflow, BookStats, HaltPolicy, DoneReason, Level, RejectReason, Order, OrderRequest, ResolvedOrder, SubmitResult, Resting, Side, Event, BookError, BookResult, OrderId};
+use tracing::{info, warn, error, instrument};
+
+// Diagnostic logging on the matching path. Building with the `hot-path` feature
+// compiles these out entirely, so not even the level-filter check runs per order.
+macro_rules! hot_debug {
+    ($($arg:tt)*) => {
+        #[cfg(not(feature = "hot-path"))]
+        { tracing::debug!($($arg)*); }
+    };
+}
+
+macro_rules! hot_trace {
+    ($($arg:tt)*) => {
+        #[cfg(not(feature = "hot-path"))]
+        { tracing::trace!($($arg)*); }
+    };
+}
+
+#[derive(Debug)]
 pub struct Book {
-    pub bids: BTreeMap<i64, VecDeque<Resting>>,
-    pub asks: BTreeMap<i64, VecDeque<Resting>>,
-    pub id_index: HashMap<u64, (Side, i64)>,
+    pub bids: BTreeMap<i64, Level>,
+    pub asks: BTreeMap<i64, Level>,
+    pub id_index: HashMap<OrderId, (Side, i64)>,
     next_order_id: u64,
+    id_allocator: Option<Arc<dyn IdAllocator>>,
+    last_trade_price: Option<i64>,
+    halted: bool,
+    halt_policy: HaltPolicy,
+    max_levels_per_side: Option<usize>,
+    min_qty: u64,
+    max_qty: u64,
+    allow_non_positive_prices: bool,
+    max_notional: Option<u128>,
+    aggregate_overflow: AggregateOverflow,
+    coalesce_fills: bool,
+    observer: Option<Box<dyn EventObserver>>,
+    audit_sink: Option<Box<dyn AuditSink>>,
+    clock: Option<Box<dyn Clock>>,
+    measure_latency: bool,
+}
+
+// The observer, audit sink and clock are consumers of the book, not part of its state
+impl PartialEq for Book {
+    fn eq(&self, other: &Self) -> bool {
+        self.bids == other.bids
+            && self.asks == other.asks
+            && self.id_index == other.id_index
+            && self.next_order_id() == other.next_order_id()
+            && self.last_trade_price == other.last_trade_price
+            && self.halted == other.halted
+            && self.halt_policy == other.halt_policy
+            && self.max_levels_per_side == other.max_levels_per_side
+            && self.min_qty == other.min_qty
+            && self.max_qty == other.max_qty
+            && self.allow_non_positive_prices == other.allow_non_positive_prices
+            && self.max_notional == other.max_notional
+            && self.aggregate_overflow == other.aggregate_overflow
+            && self.coalesce_fills == other.coalesce_fills
+    }
 }
 
+impl Eq for Book {}
+
 impl Book {
     #[instrument]
     pub fn new() -> Self {
+        Self::with_start_id(1)
+    }
+
+    // Start assigning order ids from `start` instead of 1. A book restored from a
+    // snapshot should be created with the snapshot's `next_order_id` so ids keep
+    // increasing across restarts and never collide with ones already handed out.
+    #[instrument]
+    pub fn with_start_id(start: u64) -> Self {
         // Initialising a new instance of the orderBook
         let new_book = Book { 
             bids: BTreeMap::new(), 
             asks: BTreeMap::new(), 
             id_index: HashMap::new(),
-            next_order_id: 1,
+            next_order_id: start,
+            id_allocator: None,
+            last_trade_price: None,
+            halted: false,
+            halt_policy: HaltPolicy::default(),
+            max_levels_per_side: None,
+            min_qty: 1,
+            max_qty: u64::MAX,
+            allow_non_positive_prices: false,
+            max_notional: None,
+            aggregate_overflow: AggregateOverflow::default(),
+            coalesce_fills: false,
+            observer: None,
+            audit_sink: None,
+            clock: None,
+            measure_latency: true,
         };
-        info!("Initialized new order book");
+        info!(start_id = start, "Initialized new order book");
         new_book
     }
 
-    #[instrument(level = "trace")]
+    // Take order ids from a shared allocator rather than a per-book counter, e.g.
+    // so books for different symbols never hand out the same id
+    #[instrument]
+    pub fn with_id_allocator(ids: Arc<dyn IdAllocator>) -> Self {
+        let mut book = Self::with_start_id(ids.peek());
+        book.id_allocator = Some(ids);
+        book
+    }
+
+    // Book for backtests and replays: timestamps come from `clock` and the per-order
+    // latency measurement is skipped, so a run is reproducible and carries no
+    // timing overhead. Matching is identical to `new()`. Build with the `hot-path`
+    // feature to compile out the tracing spans as well.
+    pub fn new_for_sim(clock: Box<dyn Clock>) -> Self {
+        let mut book = Self::new();
+        book.clock = Some(clock);
+        book.measure_latency = false;
+        book
+    }
+
+    // Timestamp for the next event: epoch millis, or the injected clock's reading
+    // for a sim book. Pass it to `cancel` and friends to stay on the same clock.
+    pub fn now(&self) -> u64 {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => now_ms(),
+        }
+    }
+
+    // Install an observer that sees every event inline, replacing any previous one
+    pub fn set_observer(&mut self, observer: Box<dyn EventObserver>) {
+        self.observer = Some(observer);
+    }
+
+    pub fn take_observer(&mut self) -> Option<Box<dyn EventObserver>> {
+        self.observer.take()
+    }
+
+    // Record every lifecycle transition to `sink`, replacing any previous sink
+    pub fn set_audit_sink(&mut self, sink: Box<dyn AuditSink>) {
+        self.audit_sink = Some(sink);
+    }
+
+    pub fn take_audit_sink(&mut self) -> Option<Box<dyn AuditSink>> {
+        self.audit_sink.take()
+    }
+
+    fn notify(&mut self, events: &[Event]) {
+        if let Some(observer) = self.observer.as_mut() {
+            for event in events {
+                observer.on_event(event);
+            }
+        }
+        if self.audit_sink.is_some() {
+            for event in events {
+                self.audit(AuditRecord::from_event(event));
+            }
+        }
+    }
+
+    fn audit(&mut self, records: impl IntoIterator<Item = AuditRecord>) {
+        let Some(sink) = self.audit_sink.as_mut() else { return };
+        for record in records {
+            if let Err(e) = sink.record(&record) {
+                error!(id=%record.order_id, action=?record.action, error=%e, "Failed to write audit record");
+            }
+        }
+    }
+
+    // While halted, submissions covered by the halt policy are rejected; cancels still work
+    pub fn set_halted(&mut self, halted: bool) {
+        info!(halted = halted, policy = ?self.halt_policy, "Book halt state changed");
+        self.halted = halted;
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn set_halt_policy(&mut self, policy: HaltPolicy) {
+        self.halt_policy = policy;
+    }
+
+    fn notional_error(&self, req: &OrderRequest) -> Option<BookError> {
+        let max = self.max_notional?;
+        let notional = req.price?.unsigned_abs() as u128 * req.quantity as u128;
+        (notional > max).then_some(BookError::NotionalTooLarge { notional, max })
+    }
+
+    // The first check `req` fails before it can trade or rest, if any. Used by
+    // `submit`, and by `cancel_replace` so a replacement that would be rejected
+    // never pulls the order it replaces.
+    fn pre_trade_rejection(&self, req: &OrderRequest) -> Option<(RejectReason, BookError)> {
+        if req.quantity == 0 {
+            Some((RejectReason::InvalidQuantity, BookError::InvalidQuantity { quantity: req.quantity }))
+        } else if req.quantity < self.min_qty || req.quantity > self.max_qty {
+            Some((RejectReason::SizeOutOfBounds, BookError::SizeOutOfBounds { qty: req.quantity, min: self.min_qty, max: self.max_qty }))
+        } else if req.price.is_some_and(|p| p <= 0) && !self.allow_non_positive_prices {
+            Some((RejectReason::InvalidPrice, BookError::InvalidPrice))
+        } else if let Some(error) = self.notional_error(req) {
+            Some((RejectReason::NotionalTooLarge, error))
+        } else if self.aggregate_overflow != AggregateOverflow::Saturate && self.would_overflow_level(req) {
+            Some((RejectReason::AggregateOverflow, BookError::AggregateOverflow { price: req.price.unwrap_or_default(), side: req.side }))
+        } else if self.halted && (self.halt_policy == HaltPolicy::AllOrders || self.is_aggressive(req)) {
+            Some((RejectReason::Halted, BookError::Halted))
+        } else {
+            None
+        }
+    }
+
+    // Whether `req` would take liquidity if submitted now
+    fn is_aggressive(&self, req: &OrderRequest) -> bool {
+        match (req.side, req.price) {
+            (_, None) => true,
+            (Side::BUY, Some(price)) => self.best_ask().is_some_and(|(ask, _)| price >= ask),
+            (Side::SELL, Some(price)) => self.best_bid().is_some_and(|(bid, _)| price <= bid),
+        }
+    }
+
+    // Cap the number of price levels kept on each side. When a new level pushes a
+    // side over the cap, the level furthest from the touch is evicted and its
+    // orders cancelled. None (the default) means unbounded.
+    pub fn set_max_levels_per_side(&mut self, max_levels: Option<usize>) {
+        self.max_levels_per_side = max_levels;
+    }
+
+    // Accept only orders with min_qty <= quantity <= max_qty (default 1..=u64::MAX).
+    // Applies to limit and market orders alike. An empty range (min_qty > max_qty)
+    // is refused and leaves the current limits in place.
+    pub fn set_size_limits(&mut self, min_qty: u64, max_qty: u64) -> BookResult<()> {
+        if min_qty > max_qty {
+            return Err(BookError::InvalidSizeLimits { min: min_qty, max: max_qty });
+        }
+        self.min_qty = min_qty;
+        self.max_qty = max_qty;
+        Ok(())
+    }
+
+    // Limit orders priced at or below zero are rejected unless this is enabled
+    // (e.g. for spread instruments that legitimately trade there)
+    pub fn set_allow_non_positive_prices(&mut self, allow: bool) {
+        self.allow_non_positive_prices = allow;
+    }
+
+    // Reject limit orders whose |price| * quantity exceeds `max_notional`, e.g. to
+    // keep downstream u64 cost/PnL math in range. The product is taken in u128 so
+    // the check itself can't overflow. None (the default) means no ceiling.
+    pub fn set_max_notional(&mut self, max_notional: Option<u128>) {
+        self.max_notional = max_notional;
+    }
+
+    pub fn set_aggregate_overflow(&mut self, policy: AggregateOverflow) {
+        self.aggregate_overflow = policy;
+    }
+
+    // Report one Fill per price level a taker trades at instead of one per maker in
+    // the SubmitResult handed back to the caller (see `matcher::coalesce_fills`).
+    // Off by default. Only that returned view is merged: observers, the audit trail
+    // and `id_index` bookkeeping always see one Fill per maker.
+    pub fn set_coalesce_fills(&mut self, coalesce: bool) {
+        self.coalesce_fills = coalesce;
+    }
+
+    // Live size of a level as reported by best_bid/best_ask, depth and cost queries
+    fn level_size(&self, price: i64, level: &Level) -> u64 {
+        match u64::try_from(level.total_remaining) {
+            Ok(size) => size,
+            Err(_) if self.aggregate_overflow == AggregateOverflow::Panic => panic!("aggregate size overflow at level {price}"),
+            Err(_) => u64::MAX,
+        }
+    }
+
+    // Totals built on top of level sizes (cumulative depth, queue position, notional)
+    // follow the same policy when they overflow `T`: Panic panics, and Saturate and
+    // Error clamp to `max` since a read has no order to reject
+    fn aggregate<T>(&self, checked: Option<T>, max: T, what: &str) -> T {
+        match checked {
+            Some(total) => total,
+            None if self.aggregate_overflow == AggregateOverflow::Panic => panic!("aggregate size overflow in {what}"),
+            None => max,
+        }
+    }
+
+    // Whether resting `req` in full would push its level past u64::MAX
+    fn would_overflow_level(&self, req: &OrderRequest) -> bool {
+        let Some(price) = req.price else { return false };
+        let levels = match req.side {
+            Side::BUY => &self.bids,
+            Side::SELL => &self.asks,
+        };
+        let current = levels.get(&price).map_or(0, |level| level.total_remaining);
+        current + req.quantity as u128 > u64::MAX as u128
+    }
+
+    // Price of the most recent fill, if anything has traded
+    pub fn last_price(&self) -> Option<i64> {
+        self.last_trade_price
+    }
+
+    // The id that the next submitted order will be assigned
+    pub fn next_order_id(&self) -> u64 {
+        match &self.id_allocator {
+            Some(ids) => ids.peek(),
+            None => self.next_order_id,
+        }
+    }
+
+    fn allocate_id(&mut self) -> OrderId {
+        match &self.id_allocator {
+            Some(ids) => OrderId(ids.allocate()),
+            None => {
+                let id = self.next_order_id;
+                self.next_order_id += 1;
+                OrderId(id)
+            }
+        }
+    }
+
+    pub fn stats(&self) -> BookStats {
+        BookStats {
+            bid_levels: self.bids.values().filter(|q| !q.is_empty()).count(),
+            ask_levels: self.asks.values().filter(|q| !q.is_empty()).count(),
+            total_orders: self.bids.values().chain(self.asks.values()).map(|q| q.len()).sum(),
+            next_order_id: self.next_order_id(),
+        }
+    }
+
+    #[cfg_attr(not(feature = "hot-path"), instrument(level = "trace"))]
     pub fn best_bid(&self) -> Option<(i64, u64)> {
         // Look up the highest price level on the bid side, and sum up all of the associated order quantities
-        for (price, queue) in self.bids.iter().rev() {
-            let mut counter = 0;
-            for x in queue {
-                if x.active && x.remaining > 0 {
-                    counter += x.remaining
-                }
-            }
+        for (price, level) in self.bids.iter().rev() {
+            let counter = self.level_size(*price, level);
             if counter > 0 {
-                let result = (*price, counter.try_into().unwrap_or_else(|e| {
-                    error!(counter = counter, error = %e, "Failed to convert counter to u64");
-                    0
-                }));
-                trace!(price = result.0, quantity = result.1, "Found best bid");
+                let result = (*price, counter);
+                hot_trace!(price = result.0, quantity = result.1, "Found best bid");
                 return Some(result);
             }
         }
-        trace!("No best bid found");
+        hot_trace!("No best bid found");
         None
     }
 
-    #[instrument(level = "trace")]
+    #[cfg_attr(not(feature = "hot-path"), instrument(level = "trace"))]
     pub fn best_ask(&self) -> Option<(i64, u64)> {
         // Look up the smallest value on the ask side, and sum up all the associatd quantities
-        for (price, queue) in &self.asks {
-            let mut counter = 0;
-            for x in queue {
-                if x.active && x.remaining > 0 {
-                    counter += x.remaining
-                }
-            }
+        for (price, level) in &self.asks {
+            let counter = self.level_size(*price, level);
             if counter > 0 {
-                let result = (*price, counter.try_into().unwrap_or_else(|e| {
-                    error!(counter = counter, error = %e, "Failed to convert counter to u64");
-                    0
-                }));
-                trace!(price = result.0, quantity = result.1, "Found best ask");
+                let result = (*price, counter);
+                hot_trace!(price = result.0, quantity = result.1, "Found best ask");
                 return Some(result);
             }
         }
-        trace!("No best ask found");
+        hot_trace!("No best ask found");
         None
     }
 
-    #[instrument(level = "trace")]
+    #[cfg_attr(not(feature = "hot-path"), instrument(level = "trace"))]
     pub fn spread(&self) -> Option<i64> {
         // The difference between the best bid and the best ask
         let best_bid = match self.best_bid() {
             Some((price, _)) => price,
             None => {
-                trace!("No best bid available for spread calculation");
+                hot_trace!("No best bid available for spread calculation");
                 return None;
             }
         };
@@ -88,27 +380,176 @@ impl Book {
         let best_ask = match self.best_ask() {
             Some((price, _)) => price,
             None => {
-                trace!("No best ask available for spread calculation");
+                hot_trace!("No best ask available for spread calculation");
                 return None;
             }
         };
         
         let spread = best_ask - best_bid; // Fixed: ask - bid, not bid - ask
-        trace!(best_bid = best_bid, best_ask = best_ask, spread = spread, "Calculated spread");
+        hot_trace!(best_bid = best_bid, best_ask = best_ask, spread = spread, "Calculated spread");
         Some(spread)
     }
 
-    #[instrument(skip(self, req), fields(side = ?req.side, price = ?req.price))]
-    pub fn submit(&mut self, req: &OrderRequest) -> (u64, SubmitResult) {
-        let start_time = Instant::now();
-        let now = Instant::now();
-        let ts = now.elapsed().as_secs(); 
+    // Total quote cost and worst price for a `side` taker to fill `target_qty`,
+    // walking the opposite side from the touch. None if the book can't fill the
+    // whole size (or the cost doesn't fit in a u64).
+    pub fn cumulative_cost(&self, side: Side, target_qty: u64) -> Option<(u64, i64)> {
+        if target_qty == 0 {
+            return None;
+        }
+        let levels: Box<dyn Iterator<Item = (&i64, &Level)>> = match side {
+            Side::BUY => Box::new(self.asks.iter()),
+            Side::SELL => Box::new(self.bids.iter().rev()),
+        };
+
+        let mut needed = target_qty;
+        let mut cost: u64 = 0;
+        for (price, level) in levels {
+            let available = self.level_size(*price, level);
+            if available == 0 {
+                continue;
+            }
+            let take = std::cmp::min(needed, available);
+            let level_cost = u64::try_from(*price).ok()?.checked_mul(take)?;
+            cost = cost.checked_add(level_cost)?;
+            needed -= take;
+            if needed == 0 {
+                return Some((cost, *price));
+            }
+        }
+        None
+    }
+
+    // Live size per level for prices in [lo, hi] on one side, ascending by price.
+    // Uses BTreeMap::range so only levels inside the band are visited.
+    pub fn orders_in_range(&self, side: Side, lo: i64, hi: i64) -> Vec<(i64, u64)> {
+        if lo > hi {
+            return vec![];
+        }
+        let levels = match side {
+            Side::BUY => &self.bids,
+            Side::SELL => &self.asks,
+        };
+        levels.range(lo..=hi)
+            .map(|(price, level)| (*price, self.level_size(*price, level)))
+            .filter(|(_, size)| *size > 0)
+            .collect()
+    }
+
+    // Up to `n` non-empty levels on one side as (price, size), best price first
+    pub fn depth(&self, side: Side, n: usize) -> Vec<(i64, u64)> {
+        self.depth_with_counts(side, n).into_iter().map(|(price, size, _)| (price, size)).collect()
+    }
+
+    // `depth` with each size replaced by the running total from the touch out to
+    // that level, i.e. the points of a depth chart's staircase
+    pub fn cumulative_depth(&self, side: Side, n: usize) -> Vec<(i64, u64)> {
+        let mut total: u64 = 0;
+        self.depth(side, n).into_iter().map(|(price, size)| {
+            total = self.aggregate(total.checked_add(size), u64::MAX, "cumulative depth");
+            (price, total)
+        }).collect()
+    }
+
+    // Like `depth`, plus the number of live orders making up each level
+    pub fn depth_with_counts(&self, side: Side, n: usize) -> Vec<(i64, u64, usize)> {
+        let levels: Box<dyn Iterator<Item = (&i64, &Level)>> = match side {
+            Side::BUY => Box::new(self.bids.iter().rev()),
+            Side::SELL => Box::new(self.asks.iter()),
+        };
+        levels
+            .map(|(price, level)| (*price, self.level_size(*price, level), level.iter().filter(|r| r.active && r.remaining > 0).count()))
+            .filter(|(_, size, _)| *size > 0)
+            .take(n)
+            .collect()
+    }
+
+    // CRC32 over the top `n` levels for desync checks. It hashes the book's integer
+    // ticks and sizes, so it is not comparable with a venue checksum computed over
+    // decimal price strings (OKX's, for one) unless those strings are integers.
+    // Levels are interleaved best first as bid_i then ask_i, the order OKX uses,
+    // each as `price:size`, skipping a side once it runs out, all joined with ':'
+    // (bids 100x10, 99x1 and ask 101x7 give "100:10:101:7:99:1").
+    pub fn checksum(&self, n: usize) -> u32 {
+        let bids = self.depth(Side::BUY, n);
+        let asks = self.depth(Side::SELL, n);
+        let mut canonical = vec![];
+        for i in 0..bids.len().max(asks.len()) {
+            for (price, size) in [bids.get(i), asks.get(i)].into_iter().flatten() {
+                canonical.push(format!("{price}:{size}"));
+            }
+        }
+        crc32fast::hash(canonical.join(":").as_bytes())
+    }
+
+    // Full view of a resting order. None once it has filled, been cancelled or
+    // never existed.
+    pub fn get_order(&self, id: OrderId) -> Option<ResolvedOrder> {
+        let &(side, price) = self.id_index.get(&id)?;
+        let levels = match side {
+            Side::BUY => &self.bids,
+            Side::SELL => &self.asks,
+        };
+        let resting = levels.get(&price)?.iter().find(|r| r.id == id)?;
+        Some(ResolvedOrder {
+            id,
+            client_id: resting.client_id,
+            side,
+            price,
+            quantity: resting.quantity,
+            remaining: resting.remaining,
+            ts: resting.ts,
+        })
+    }
+
+    // Live size queued ahead of a resting order at its price level. None if the id
+    // isn't resting.
+    pub fn queue_ahead(&self, id: OrderId) -> Option<u64> {
+        let &(side, price) = self.id_index.get(&id)?;
+        let levels = match side {
+            Side::BUY => &self.bids,
+            Side::SELL => &self.asks,
+        };
+        let mut ahead: u64 = 0;
+        for resting in levels.get(&price)?.iter() {
+            if resting.id == id {
+                return Some(ahead);
+            }
+            if resting.active {
+                ahead = self.aggregate(ahead.checked_add(resting.remaining), u64::MAX, "queue position");
+            }
+        }
+        None
+    }
+
+    // Sum of |price| * remaining over live orders on one side, widened to u128 so
+    // a deep book can't overflow. Negative prices (allowed with
+    // `set_allow_non_positive_prices`) count by magnitude, so this is gross
+    // exposure rather than a signed value.
+    pub fn resting_notional(&self, side: Side) -> u128 {
+        let levels = match side {
+            Side::BUY => &self.bids,
+            Side::SELL => &self.asks,
+        };
+        levels.iter().fold(0u128, |total, (price, level)| {
+            let notional = self.aggregate((price.unsigned_abs() as u128).checked_mul(level.total_remaining), u128::MAX, "resting notional");
+            self.aggregate(total.checked_add(notional), u128::MAX, "resting notional")
+        })
+    }
+
+    #[cfg_attr(not(feature = "hot-path"), instrument(skip(self, req), fields(side = ?req.side, price = ?req.price)))]
+    pub fn submit(&mut self, req: &OrderRequest) -> (OrderId, SubmitResult) {
+        #[cfg(not(feature = "hot-path"))]
+        let start_time = self.measure_latency.then(std::time::Instant::now);
+        let ts = self.now();
         
         // Generate unique order ID
-        let order_id = self.next_order_id;
-        self.next_order_id += 1;
+        let order_id = self.allocate_id();
         
-        debug!(id=order_id, ?req.side, price=?req.price, qty=req.quantity, "Processing order submission");
+        hot_debug!(id=%order_id, ?req.side, price=?req.price, qty=req.quantity, "Processing order submission");
+        if self.audit_sink.is_some() {
+            self.audit([AuditRecord::received(order_id, req, ts)]);
+        }
         
         // Create internal Order with generated ID
         let o = Order {
@@ -116,112 +557,126 @@ impl Book {
             price: req.price,
             quantity: req.quantity,
             side: req.side,
+            client_id: req.client_id,
         };
         
-        let result = if req.quantity == 0 {
-            let error = BookError::InvalidQuantity { quantity: req.quantity };
-            warn!(id=order_id, qty=req.quantity, error=%error, "Rejecting order with invalid quantity");
-            SubmitResult {
-                events: vec![Event::Done {id: order_id, reason: DoneReason::Rejected, ts}]
+        let result = if let Some((reason, error)) = self.pre_trade_rejection(req) {
+            if reason == RejectReason::AggregateOverflow && self.aggregate_overflow == AggregateOverflow::Panic {
+                panic!("aggregate size overflow: {error}");
             }
+            warn!(id=%order_id, price=?req.price, qty=req.quantity, error=%error, "Rejecting order");
+            SubmitResult::new(order_id, vec![Event::Done {id: order_id, client_id: req.client_id, reason: DoneReason::Rejected(reason), remaining: req.quantity, ts}])
         } else if req.price.is_none() {
             // MARKET ORDERS
-            debug!(id=order_id, "Processing market order");
+            hot_debug!(id=%order_id, "Processing market order");
             self.execute_market_order(&o, ts)
         } else {
             // LIMIT ORDERS
-            debug!(id=order_id, price=?req.price, "Processing limit order");
+            hot_debug!(id=%order_id, price=?req.price, "Processing limit order");
             self.execute_limit_order(&o, ts)
         };
         
-        let processing_time = start_time.elapsed();
-        debug!(
-            id=order_id, 
-            processing_time_ns = processing_time.as_nanos(),
+        hot_debug!(
+            id=%order_id, 
+            processing_time_ns = ?start_time.map(|t| t.elapsed().as_nanos()),
             events_count = result.events.len(),
             "Order processing completed"
         );
         
-        (order_id, result)
+        self.notify(&result.events);
+        (order_id, self.present(order_id, result))
+    }
+
+    // What the caller gets back for `id` once observers and audit have seen `result`
+    fn present(&self, id: OrderId, result: SubmitResult) -> SubmitResult {
+        if self.coalesce_fills {
+            SubmitResult::new(id, coalesce_fills(result.events))
+        } else {
+            result
+        }
+    }
+
+    // Submit several orders in one call. Non-atomic, this is `submit` for each in
+    // turn. Atomic, the batch is run against the book and, if any order comes back
+    // Rejected, the book is put back exactly as it was and every other order in the
+    // batch is reported Rejected(BatchRolledBack). Observers and the audit trail
+    // only see the outcome that stands. Ids given to a rolled-back batch are not
+    // reused. An atomic batch copies the book's levels, so it costs O(book) on top
+    // of the matching.
+    pub fn submit_batch(&mut self, reqs: &[OrderRequest], atomic: bool) -> Vec<(OrderId, SubmitResult)> {
+        if !atomic {
+            return reqs.iter().map(|req| self.submit(req)).collect();
+        }
+
+        let saved = (self.bids.clone(), self.asks.clone(), self.id_index.clone(), self.last_trade_price);
+        let observer = self.observer.take();
+        let audit_sink = self.audit_sink.take();
+        // Merged only after notify below, so keep per-maker fills until then
+        let coalesce = std::mem::replace(&mut self.coalesce_fills, false);
+        let mut results: Vec<(OrderId, SubmitResult)> = reqs.iter().map(|req| self.submit(req)).collect();
+
+        let rejected = |result: &SubmitResult| matches!(result.terminal_reason, Some(DoneReason::Rejected(_)));
+        if results.iter().any(|(_, result)| rejected(result)) {
+            (self.bids, self.asks, self.id_index, self.last_trade_price) = saved;
+            let ts = self.now();
+            for ((id, result), req) in results.iter_mut().zip(reqs) {
+                if !rejected(result) {
+                    *result = SubmitResult::new(*id, vec![Event::Done {id: *id, client_id: req.client_id, reason: DoneReason::Rejected(RejectReason::BatchRolledBack), remaining: req.quantity, ts}]);
+                }
+            }
+            warn!(orders = reqs.len(), "Atomic batch rejected and rolled back");
+        }
+
+        self.observer = observer;
+        self.audit_sink = audit_sink;
+        self.coalesce_fills = coalesce;
+        for ((id, result), req) in results.iter().zip(reqs) {
+            if self.audit_sink.is_some() {
+                let ts = result.events.first().map_or_else(|| self.now(), Event::ts);
+                self.audit([AuditRecord::received(*id, req, ts)]);
+            }
+            self.notify(&result.events);
+        }
+        self.debug_assert_invariants();
+        results.into_iter().map(|(id, result)| (id, self.present(id, result))).collect()
     }
 
-    #[instrument(skip(self, o), fields(order_id = o.id, side = ?o.side, price = ?o.price))]
+    #[cfg_attr(not(feature = "hot-path"), instrument(skip(self, o), fields(order_id = %o.id, side = ?o.side, price = ?o.price)))]
     pub fn execute_limit_order(&mut self, o: &Order, ts: u64) -> SubmitResult {
         let price = match o.price {
             Some(p) => p,
             None => {
-                error!(id=o.id, "Limit order missing price");
-                return SubmitResult {
-                    events: vec![Event::Done {id: o.id, reason: DoneReason::Rejected, ts}]
-                };
+                error!(id=%o.id, "Limit order missing price");
+                return SubmitResult::new(o.id, vec![Event::Done {id: o.id, client_id: o.client_id, reason: DoneReason::Rejected(RejectReason::InvalidPrice), remaining: o.quantity, ts}]);
             }
         };
-        
-        debug!(id=o.id, side=?o.side, price=price, qty=o.quantity, "Resting limit order");
 
-        // Match on whether it's a buy or sell limit order
-        // Initialise counter
-        // Look up the price level for the bid or ask side
-        // While the counter is less than order quantity and the counter is greater that the order quantity,
-        // iterate through each element in the VecDeque at that price level and remove the resting order from the queue
+        // Walk the opposite side from the touch while its price is at or better than
+        // our limit, then rest whatever is left at our price
         let mut events: Vec<Event> = vec![];
-        match o.side {
-            Side::BUY => {
-                let mut remaining_qty = o.quantity;
-                if let Some(queue) = self.asks.get_mut(&price) {
-                    for resting in queue.iter_mut() {
-                        if remaining_qty == 0 { break; }
-                        let fill_qty = std::cmp::min(remaining_qty, resting.remaining);
-                        resting.remaining -= fill_qty;
-                        remaining_qty -= fill_qty;
-                        events.push(Event::Fill {
-                            taker_id: o.id,
-                            maker_id: resting.id,
-                            price,
-                            qty: fill_qty,
-                            ts
-                        });
-                        debug!(taker_id=o.id, maker_id=resting.id, price=price, qty=fill_qty, "Fill executed");
-                    }
+        let remaining_qty = self.sweep(o, ts, &mut events);
 
-                }
-            },
-            Side::SELL => {
-                let mut remaining_qty = o.quantity;
-                if let Some(queue) = self.bids.get_mut(&price) {
-                    for resting in queue.iter_mut() {
-                        if remaining_qty == 0 { break; }
-                        let fill_qty = std::cmp::min(remaining_qty, resting.remaining);
-                        resting.remaining -= fill_qty;
-                        remaining_qty -= fill_qty;
-                        events.push(Event::Fill {
-                            taker_id: o.id,
-                            maker_id: resting.id,
-                            price,
-                            qty: fill_qty,
-                            ts
-                        });
-                        debug!(taker_id=o.id, maker_id=resting.id, price=price, qty=fill_qty, "Fill executed");
-                    }
-
-                }
-            }
+        if remaining_qty == 0 {
+            hot_debug!(id=%o.id, "Limit order fully filled");
+            events.push(Event::Done {id: o.id, client_id: o.client_id, reason: DoneReason::Filled, remaining: 0, ts});
+        } else {
+            hot_debug!(id=%o.id, side=?o.side, price=price, qty=remaining_qty, "Resting limit order");
+            let resting_result = self.add_resting_order(o, price, remaining_qty, ts);
+            events.extend(resting_result.events);
         }
-
-        // Add the resting order and combine events
-        let resting_result = self.add_resting_order(o, price, ts);
-        events.extend(resting_result.events);
-        SubmitResult { events }
+        self.debug_assert_invariants();
+        SubmitResult::new(o.id, events)
     }
 
-    fn add_resting_order(&mut self, o: &Order, price: i64, ts: u64) -> SubmitResult {
+    fn add_resting_order(&mut self, o: &Order, price: i64, remaining: u64, ts: u64) -> SubmitResult {
         let resting = Resting {
             id: o.id,
             price: o.price, 
-            remaining: o.quantity,
+            remaining,
             ts,
             active: true,
             quantity: o.quantity, 
+            client_id: o.client_id,
         };
 
         let level_map = match o.side {
@@ -229,120 +684,125 @@ impl Book {
             Side::SELL => &mut self.asks,
         };
 
-        if let Some(existing_queue) = level_map.get_mut(&price) {
-            existing_queue.push_back(resting);
-        } else {
-            let mut queue = VecDeque::new();
-            queue.push_back(resting);
-            level_map.insert(price, queue);
-        }
+        level_map.entry(price).or_default().push_back(resting);
         
         let order_id = o.id;
         let side = o.side;
         self.id_index.insert(order_id, (side, price));
-        debug!(id=order_id, price=price, side=?side, "Added order to book");
+        hot_debug!(id=%order_id, price=price, side=?side, "Added order to book");
+
+        let mut events = vec![Event::Done {id: o.id, client_id: o.client_id, reason: DoneReason::Rested, remaining, ts}];
+        self.enforce_level_cap(side, ts, &mut events);
+        SubmitResult::new(o.id, events)
+    }
+
+    fn enforce_level_cap(&mut self, side: Side, ts: u64, events: &mut Vec<Event>) {
+        let Some(max_levels) = self.max_levels_per_side else { return };
+        let levels = match side {
+            Side::BUY => &mut self.bids,
+            Side::SELL => &mut self.asks,
+        };
+        levels.retain(|_, level| !level.is_empty());
 
-        SubmitResult {
-            events: vec![Event::Done {id: o.id, reason: DoneReason::Rested, ts}]
+        while levels.len() > max_levels {
+            // Furthest from the touch: lowest bid, highest ask
+            let evicted = match side {
+                Side::BUY => levels.pop_first(),
+                Side::SELL => levels.pop_last(),
+            };
+            let Some((price, level)) = evicted else { break };
+            warn!(price=price, side=?side, orders=level.len(), max_levels=max_levels, "Evicting price level over depth cap");
+            for resting in level.orders {
+                self.id_index.remove(&resting.id);
+                events.push(Event::Done {id: resting.id, client_id: resting.client_id, reason: DoneReason::Cancelled, remaining: resting.remaining, ts});
+            }
         }
     }
 
-    #[instrument(skip(self, o), fields(order_id = o.id, side = ?o.side, price = ?o.price))]
+    #[cfg_attr(not(feature = "hot-path"), instrument(skip(self, o), fields(order_id = %o.id, side = ?o.side, price = ?o.price)))]
     pub fn execute_market_order(&mut self, o: &Order, ts: u64) -> SubmitResult {
-        debug!(id=o.id, qty=o.quantity, side=?o.side, "Executing market order");
+        hot_debug!(id=%o.id, qty=o.quantity, side=?o.side, "Executing market order");
         
         let mut events = vec![];
-        let remaining_qty = match o.side {
-            Side::BUY => self.execute_market_buy(o.id, o.quantity, ts, &mut events),
-            Side::SELL => self.execute_market_sell(o.id, o.quantity, ts, &mut events),
+        let has_liquidity = match o.side {
+            Side::BUY => self.best_ask().is_some(),
+            Side::SELL => self.best_bid().is_some(),
+        };
+        let remaining_qty = if has_liquidity {
+            self.sweep(o, ts, &mut events)
+        } else {
+            let error = BookError::NoLiquidity { side: o.side };
+            warn!(id=%o.id, error=%error, "No liquidity available for market order");
+            o.quantity
         };
         
-        self.finalize_market_order(o.id, o.quantity, remaining_qty, ts, &mut events);
-        SubmitResult { events }
+        self.finalize_market_order(o, remaining_qty, ts, &mut events);
+        self.debug_assert_invariants();
+        SubmitResult::new(o.id, events)
     }
 
-    fn execute_market_buy(&mut self, order_id: u64, quantity: u64, ts: u64, events: &mut Vec<Event>) -> u64 {
-        let best_ask_price = match self.best_ask() {
-            Some((price, _)) => price,
-            None => {
-                let error = BookError::NoLiquidity { side: Side::BUY };
-                warn!(id=order_id, error=%error, "No liquidity available for market BUY order");
-                return quantity; // Return all remaining quantity
-            }
-        };
-        
-        let queue = match self.asks.get_mut(&best_ask_price) {
-            Some(queue) => queue,
-            None => {
-                error!(id=order_id, price=best_ask_price, "Best ask level not found");
-                return quantity; // Return all remaining quantity
-            }
-        };
-        
-        Self::fill_against_level(order_id, quantity, best_ask_price, queue, ts, events)
+    // Apply a trade that happened on an external venue to our resting orders: a
+    // `side` aggressor for `qty` at `price` takes our opposite orders priced at or
+    // better than `price`, exactly as an internal order would. The Fills name
+    // `OrderId::EXTERNAL` as taker, which never rests and gets no Done, and no id
+    // is allocated for it; whatever our book can't absorb is simply not reported.
+    pub fn apply_external_fill(&mut self, side: Side, price: i64, qty: u64, ts: u64) -> Vec<Event> {
+        if qty == 0 {
+            return vec![];
+        }
+        let taker = Order { id: OrderId::EXTERNAL, price: Some(price), quantity: qty, side, client_id: None };
+        let mut events = vec![];
+        self.sweep(&taker, ts, &mut events);
+        hot_debug!(id=%taker.id, ?side, price=price, qty=qty, "Applied external fill");
+        self.debug_assert_invariants();
+        self.notify(&events);
+        events
     }
 
-    fn execute_market_sell(&mut self, order_id: u64, quantity: u64, ts: u64, events: &mut Vec<Event>) -> u64 {
-        let best_bid_price = match self.best_bid() {
-            Some((price, _)) => price,
-            None => {
-                let error = BookError::NoLiquidity { side: Side::SELL };
-                warn!(id=order_id, error=%error, "No liquidity available for market SELL order");
-                return quantity; // Return all remaining quantity
-            }
-        };
-        
-        let queue = match self.bids.get_mut(&best_bid_price) {
-            Some(queue) => queue,
-            None => {
-                error!(id=order_id, price=best_bid_price, "Best bid level not found");
-                return quantity; // Return all remaining quantity
-            }
-        };
-        
-        Self::fill_against_level(order_id, quantity, best_bid_price, queue, ts, events)
-    }
-
-    fn fill_against_level(taker_id: u64, mut remaining_qty: u64, price: i64, queue: &mut VecDeque<Resting>, ts: u64, events: &mut Vec<Event>) -> u64 {
-        for resting_order in queue {
-            if resting_order.active && resting_order.remaining > 0 && remaining_qty > 0 {
-                let fill_qty = std::cmp::min(remaining_qty, resting_order.remaining);
-                resting_order.remaining -= fill_qty;
-                remaining_qty -= fill_qty;
-                
-                debug!(taker_id=taker_id, maker_id=resting_order.id, price=price, qty=fill_qty, "Fill executed");
-                
-                events.push(Event::Fill {
-                    taker_id, 
-                    maker_id: resting_order.id, 
-                    price, 
-                    qty: fill_qty, 
-                    ts
-                });
-                
-                if remaining_qty == 0 { break; }
+    // Match a taker via `matcher::match_order`, then bring the rest of the book in
+    // line: emptied makers leave `id_index` and the last fill sets the last trade
+    // price. Returns the taker's unfilled quantity.
+    fn sweep(&mut self, taker: &Order, ts: u64, events: &mut Vec<Event>) -> u64 {
+        let (fills, remaining_qty) = match_order(&mut self.bids, &mut self.asks, taker, ts);
+        #[cfg(not(feature = "hot-path"))]
+        for event in &fills {
+            if let Event::Fill { maker_id, price, qty, .. } = event {
+                tracing::debug!(taker_id=%taker.id, maker_id=%maker_id, price=price, qty=qty, "Fill executed");
             }
         }
+        if let Some(price) = fills.iter().rev().find_map(|e| match e { Event::Fill { price, .. } => Some(*price), _ => None }) {
+            self.last_trade_price = Some(price);
+        }
+        for maker_id in exhausted_makers(&fills, taker.id) {
+            self.id_index.remove(&maker_id);
+        }
+        events.extend(fills);
         remaining_qty
     }
 
-    fn finalize_market_order(&self, order_id: u64, _original_qty: u64, remaining_qty: u64, ts: u64, events: &mut Vec<Event>) {
+    fn finalize_market_order(&self, o: &Order, remaining_qty: u64, ts: u64, events: &mut Vec<Event>) {
+        let order_id = o.id;
         if !events.is_empty() {
             if remaining_qty == 0 {
-                events.push(Event::Done {id: order_id, reason: DoneReason::Filled, ts});
-                debug!(id=order_id, "Market order fully filled");
+                events.push(Event::Done {id: order_id, client_id: o.client_id, reason: DoneReason::Filled, remaining: 0, ts});
+                hot_debug!(id=%order_id, "Market order fully filled");
             } else {
-                events.push(Event::Done {id: order_id, reason: DoneReason::Rejected, ts});
-                warn!(id=order_id, remaining_qty=remaining_qty, "Market order partially filled - insufficient liquidity");
+                events.push(Event::Done {id: order_id, client_id: o.client_id, reason: DoneReason::PartiallyFilledThenCancelled, remaining: remaining_qty, ts});
+                warn!(id=%order_id, remaining_qty=remaining_qty, "Market order partially filled - insufficient liquidity, residual cancelled");
             }
         } else {
-            warn!(id=order_id, "Market order rejected - no fills executed");
-            events.push(Event::Done {id: order_id, reason: DoneReason::Rejected, ts});
+            warn!(id=%order_id, "Market order rejected - no fills executed");
+            events.push(Event::Done {id: order_id, client_id: o.client_id, reason: DoneReason::Rejected(RejectReason::NoLiquidity), remaining: remaining_qty, ts});
         }
     }
 
     pub fn cancel_limit_order(&mut self, o: Order, ts: u64) -> Option<SubmitResult> {
-        debug!(id=o.id, "Attempting to cancel limit order");
+        self.cancel(o.id, ts)
+    }
+
+    // Cancel a resting order by id. None if the id isn't resting.
+    pub fn cancel(&mut self, id: OrderId, ts: u64) -> Option<SubmitResult> {
+        hot_debug!(id=%id, "Attempting to cancel limit order");
         // Look up order id in id_index hashmap
         // Extract the tuple represeting the (Side, Price)
         // Remove this entry from the Hashmap
@@ -351,69 +811,172 @@ impl Book {
         // Look up the price inside the Level struct to get to the queue 
         // Iterate through the VecDeque object until we find one where the corresponding resting.id matches the order id
         // Remove the resting order from Level VecDeque
-        if let Some(&(side, price)) = self.id_index.get(&o.id) {
-            debug!(id=o.id, price=price, side=?side, "Cancelling limit order");
-            self.id_index.remove(&o.id);
-            match side {
-                Side::BUY => {
-                    if let Some(queue) = self.bids.get_mut(&price) {
-                        let mut counter = 0;
-                        for order in queue.iter() {
-                            if order.id == o.id {
-                                debug!(?queue, "Found limit order to cancel");
-                                queue.remove(counter);
-                                debug!(?queue, "Limit order cancelled");
-                                break;
-
-                            }
-
-                            counter += 1;
-                        }
-
-                        Some(SubmitResult {events: vec![Event::Done {id: o.id, reason: DoneReason::Cancelled, ts}]})
-                    }
-
-                    else {
-                        None
-                    }
-                }
+        let result = if let Some(&(side, price)) = self.id_index.get(&id) {
+            hot_debug!(id=%id, price=price, side=?side, "Cancelling limit order");
+            self.id_index.remove(&id);
+            let levels = match side {
+                Side::BUY => &mut self.bids,
+                Side::SELL => &mut self.asks,
+            };
+            // Report what was still open at cancel time, not the original size
+            levels.get_mut(&price)
+                .and_then(|level| level.remove(id))
+                .map(|cancelled| {
+                    hot_debug!(id=%id, remaining=cancelled.remaining, "Limit order cancelled");
+                    SubmitResult::new(id, vec![Event::Done {id, client_id: cancelled.client_id, reason: DoneReason::Cancelled, remaining: cancelled.remaining, ts}])
+                })
+        }
 
-                Side::SELL => {
-                    if let Some(queue) = self.asks.get_mut(&price) {
-                        let mut counter = 0;
-                        for order in queue.iter() {
-                            if order.id == o.id {
-                                queue.remove(counter);
-                                break;
-                            }
+        else {
+            None
+        };
 
-                            counter += 1;
-                        }
-                        Some(SubmitResult {events: vec![Event::Done {id: o.id, reason: DoneReason::Cancelled, ts}]})
+        self.debug_assert_invariants();
+        if let Some(cancelled) = &result {
+            self.notify(&cancelled.events);
+        }
+        result
+    }
 
-                    }
+    // Replace a resting order with `new_req`. If the only change is a size reduction
+    // at the same side, price and client id, within the size limits, the order is
+    // amended in place: it keeps its id and queue position and `new_req.quantity`
+    // becomes its open size. Anything else cancels the order and submits `new_req`
+    // under a new id. The replace is atomic: if `new_req` would be rejected (judged
+    // against the book with the original still resting), the original is left as
+    // it was and the rejection is reported under the new id.
+    pub fn cancel_replace(&mut self, id: OrderId, new_req: &OrderRequest) -> (OrderId, SubmitResult) {
+        let ts = self.now();
+        let Some(&(side, price)) = self.id_index.get(&id) else {
+            let error = BookError::OrderNotFound { id };
+            warn!(id=%id, error=%error, "Cancel-replace of unknown order");
+            let result = SubmitResult::new(id, vec![Event::Done {id, client_id: new_req.client_id, reason: DoneReason::Rejected(RejectReason::OrderNotFound), remaining: new_req.quantity, ts}]);
+            self.notify(&result.events);
+            return (id, result);
+        };
 
-                    else {
-                        None
-                    }
-                }
+        let same_client = self.get_order(id).is_some_and(|o| o.client_id == new_req.client_id);
+        let within_limits = (self.min_qty..=self.max_qty).contains(&new_req.quantity);
+        if new_req.side == side && new_req.price == Some(price) && new_req.quantity > 0 && same_client && within_limits {
+            if let Some(result) = self.reduce_in_place(id, side, price, new_req.quantity, ts) {
+                return (id, result);
             }
         }
 
-        else {
-            None
+        if self.pre_trade_rejection(new_req).is_some() {
+            // Nothing has been touched yet, so submit rejects it the same way
+            return self.submit(new_req);
+        }
+
+        let mut events = self.cancel(id, ts).map(|r| r.events).unwrap_or_default();
+        let (new_id, replacement) = self.submit(new_req);
+        events.extend(replacement.events);
+        (new_id, SubmitResult::new(new_id, events))
+    }
+
+    // Trim a resting order's open size by `reduce_by`, keeping its id and queue
+    // position. Reducing by its whole remaining size (or more) cancels it. None if
+    // the id isn't resting.
+    pub fn reduce_order(&mut self, id: OrderId, reduce_by: u64, ts: u64) -> Option<SubmitResult> {
+        let &(side, price) = self.id_index.get(&id)?;
+        let levels = match side {
+            Side::BUY => &self.bids,
+            Side::SELL => &self.asks,
+        };
+        let remaining = levels.get(&price)?.iter().find(|r| r.id == id)?.remaining;
+        if reduce_by >= remaining {
+            return self.cancel(id, ts);
+        }
+        self.reduce_in_place(id, side, price, remaining - reduce_by, ts)
+    }
+
+    fn reduce_in_place(&mut self, id: OrderId, side: Side, price: i64, new_remaining: u64, ts: u64) -> Option<SubmitResult> {
+        let levels = match side {
+            Side::BUY => &mut self.bids,
+            Side::SELL => &mut self.asks,
+        };
+        let resting = levels.get_mut(&price)?.reduce_to(id, new_remaining)?;
+        hot_debug!(id=%id, remaining=resting.remaining, "Reduced order in place");
+        let result = SubmitResult::new(id, vec![Event::Done {id, client_id: resting.client_id, reason: DoneReason::Rested, remaining: new_remaining, ts}]);
+        self.debug_assert_invariants();
+        self.notify(&result.events);
+        Some(result)
+    }
+
+    // Equality over what the book would actually match against: live resting orders
+    // per price level in queue order, plus `next_order_id`. Inactive entries, empty
+    // levels and other bookkeeping (observer, halt state, last trade) are ignored,
+    // which makes this the right comparison for rebuilt or restored books.
+    pub fn semantically_eq(&self, other: &Book) -> bool {
+        fn live_orders(levels: &BTreeMap<i64, Level>) -> impl Iterator<Item = (i64, &Resting)> {
+            levels.iter().flat_map(|(price, level)| level.iter().filter(|r| r.active).map(move |r| (*price, r)))
         }
 
+        self.next_order_id() == other.next_order_id()
+            && live_orders(&self.bids).eq(live_orders(&other.bids))
+            && live_orders(&self.asks).eq(live_orders(&other.asks))
     }
 
+    // Structural consistency checks for the book:
+    //  - the book is not crossed (best bid < best ask)
+    //  - `id_index` has exactly one entry per order sitting in a level
+    //  - every `id_index` entry points at an existing level that contains that id
+    //  - each level's cached `total_remaining` matches the live size of its orders
+    pub fn check_invariants(&self) -> Result<(), String> {
+        if let (Some((bid, _)), Some((ask, _))) = (self.best_bid(), self.best_ask()) {
+            if bid >= ask {
+                return Err(format!("book is crossed: best bid {bid} >= best ask {ask}"));
+            }
+        }
 
-}
+        let resting_count: usize = self.bids.values().chain(self.asks.values()).map(|q| q.len()).sum();
+        if resting_count != self.id_index.len() {
+            return Err(format!(
+                "id_index has {} entries but {} orders are resting",
+                self.id_index.len(), resting_count
+            ));
+        }
 
+        for (id, &(side, price)) in &self.id_index {
+            let levels = match side {
+                Side::BUY => &self.bids,
+                Side::SELL => &self.asks,
+            };
+            match levels.get(&price) {
+                Some(level) if level.iter().any(|r| r.id == *id) => {}
+                Some(_) => return Err(format!("order {id} not found in {side:?} level {price}")),
+                None => return Err(format!("order {id} points at missing {side:?} level {price}")),
+            }
+        }
+
+        for (price, level) in self.bids.iter().chain(self.asks.iter()) {
+            let live: u128 = level.iter().filter(|r| r.active).map(|r| r.remaining as u128).sum();
+            if live != level.total_remaining {
+                return Err(format!("level {price} caches size {} but its orders add up to {live}", level.total_remaining));
+            }
+        }
+        Ok(())
+    }
+
+    // Runs `check_invariants` after every mutating operation in debug/test builds only
+    fn debug_assert_invariants(&self) {
+        #[cfg(debug_assertions)]
+        if let Err(msg) = self.check_invariants() {
+            panic!("book invariant violated: {msg}");
+        }
+    }
+}
 
+impl Default for Book {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::engine::clock::SimClock;
     use std::sync::Once;
     use tracing_subscriber::EnvFilter;
 
@@ -431,6 +994,29 @@ mod tests {
         });
     }
 
+    // Book whose events all carry ts 0, for tests that compare whole events
+    fn fixed_clock_book() -> Book {
+        Book::new_for_sim(Box::new(SimClock::new(0, 0)))
+    }
+
+    #[test]
+    fn test_default_timestamps_are_epoch_millis() {
+        let mut book = Book::new();
+        let ts = |events: &[Event]| match events.last() {
+            Some(Event::Done {ts, ..}) => *ts,
+            other => panic!("expected a Done, got {other:?}"),
+        };
+        let first = ts(&book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 1, client_id: None }).1.events);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let second = ts(&book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 1, client_id: None }).1.events);
+
+        // Somewhere after 2020-01-01 and not in the future
+        assert!((1_577_836_800_000..=now_ms()).contains(&first), "{first} is not epoch millis");
+        assert!(second > first);
+        let resting = book.bids[&10].iter().map(|r| r.ts).collect::<Vec<_>>();
+        assert_eq!(resting, vec![first, second]);
+    }
+
     #[test]
     fn test_initialise() {
         let book = Book::new();
@@ -448,7 +1034,7 @@ mod tests {
         
         // Add a real bid and test
         let mut book_with_bid = Book::new();
-        let req = OrderRequest { side: Side::BUY, price: Some(100), quantity: 10 };
+        let req = OrderRequest { side: Side::BUY, price: Some(100), quantity: 10, client_id: None };
         book_with_bid.submit(&req);
         let best_bid = book_with_bid.best_bid().unwrap().0;
         assert_eq!(best_bid, 100);
@@ -457,9 +1043,9 @@ mod tests {
     #[test]
     fn test_submit_event() {
         let mut book = Book::new();
-        let req = OrderRequest { side: Side::BUY, price: Some(100), quantity: 10 };
+        let req = OrderRequest { side: Side::BUY, price: Some(100), quantity: 10, client_id: None };
         let (order_id, result) = book.submit(&req);
-        assert_eq!(order_id, 1); // First order should have ID 1
+        assert_eq!(order_id, OrderId(1)); // First order should have ID 1
         assert_eq!(result.events.len(), 1);
         // if let Event::Ack { id, .. } = result.events[0] {
         //     assert_eq!(id, 1);
@@ -470,24 +1056,24 @@ mod tests {
 
     #[test]
     fn test_market_order_fill() {
-        let now = Instant::now();
-        let ts = now.elapsed().as_secs(); 
-        let mut book = Book::new();
-        let req1 = OrderRequest {side: Side::SELL, price: Some(10), quantity: 100 };
+        let mut book = fixed_clock_book();
+        let ts = book.now();
+        let req1 = OrderRequest {side: Side::SELL, price: Some(10), quantity: 100, client_id: None };
         book.submit(&req1);
-        let req2 = OrderRequest {side: Side::BUY, price: None, quantity: 10};
+        let req2 = OrderRequest {side: Side::BUY, price: None, quantity: 10, client_id: None};
         book.submit(&req2);
         let mut fake_asks = BTreeMap::new();
-        let mut queue = VecDeque::new();
-        queue.push_back(Resting {
-            id: 1, // First order gets ID 1
+        let mut level = Level::new();
+        level.push_back(Resting {
+            id: OrderId(1), // First order gets ID 1
             price: Some(10), 
             remaining: 90,
             ts,
             active: true,
             quantity: 100, 
+            client_id: None,
         });
-        fake_asks.insert(10, queue);
+        fake_asks.insert(10, level);
 
         assert_eq!(book.asks, fake_asks);
 
@@ -496,30 +1082,30 @@ mod tests {
     #[test]
     fn test_cancel_market_order() {
         init_tracing();
-        let now = Instant::now();
-        let ts = now.elapsed().as_secs(); 
-        let mut book = Book::new();
-        let req1 = OrderRequest {side: Side::BUY, price: Some(10), quantity: 100 };
+        let mut book = fixed_clock_book();
+        let ts = book.now();
+        let req1 = OrderRequest {side: Side::BUY, price: Some(10), quantity: 100, client_id: None };
         let (order_id, _) = book.submit(&req1);
-        let order1 = Order {id: order_id, side: Side::BUY, price: Some(10), quantity: 100 };
+        let order1 = Order {id: order_id, side: Side::BUY, price: Some(10), quantity: 100, client_id: None };
         let mut fake_bids = BTreeMap::new();
-        let mut queue = VecDeque::new();
-        queue.push_back(Resting {
+        let mut level = Level::new();
+        level.push_back(Resting {
             id: order_id,
             price: Some(10), 
             remaining: 100,
             ts,
             active: true,
             quantity: 100, 
+            client_id: None,
         });
-        fake_bids.insert(10, queue);
+        fake_bids.insert(10, level);
 
         assert_eq!(book.bids, fake_bids);
 
         book.cancel_limit_order(order1.clone(), ts);
 
-        if let Some(queue) = fake_bids.get_mut(&10) {
-            queue.retain(|r| r.id != order1.id); // remove just that order
+        if let Some(level) = fake_bids.get_mut(&10) {
+            level.remove(order1.id); // remove just that order
         }
 
         assert_eq!(book.bids, fake_bids);
@@ -527,41 +1113,996 @@ mod tests {
     }
 
     #[test]
-    fn test_limit_order_matching() {
-        let now = Instant::now();
-        let ts = now.elapsed().as_secs(); 
+    fn test_cancel_reports_unfilled_remainder() {
         let mut book = Book::new();
-        let req1 = OrderRequest {side: Side::SELL, price: Some(10), quantity: 100 };
+        let (maker_id, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 100, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 35, client_id: None });
+
+        let maker = Order {id: maker_id, side: Side::SELL, price: Some(10), quantity: 100, client_id: None };
+        let result = book.cancel_limit_order(maker.clone(), 0).expect("maker should still be resting");
+        assert_eq!(result.events, vec![Event::Done {id: maker_id, client_id: None, reason: DoneReason::Cancelled, remaining: 65, ts: 0}]);
+
+        // Second cancel finds nothing
+        assert!(book.cancel_limit_order(maker, 0).is_none());
+    }
+
+    #[test]
+    fn test_limit_order_matching() {
+        let mut book = fixed_clock_book();
+        let ts = book.now();
+        let req1 = OrderRequest {side: Side::SELL, price: Some(10), quantity: 100, client_id: None };
         let (maker_id, _) = book.submit(&req1);
-        let req2 = OrderRequest {side: Side::BUY, price: Some(10), quantity: 10};
+        let req2 = OrderRequest {side: Side::BUY, price: Some(10), quantity: 10, client_id: None};
         let (taker_id, result) = book.submit(&req2);
         assert_eq!(result.events.len(), 2);
-        assert_eq!(result.events[0], Event::Fill {taker_id, maker_id, price: 10, qty: 10, ts});
-        assert_eq!(result.events[1], Event::Done {id: taker_id, reason: DoneReason::Rested, ts});
+        assert_eq!(result.events[0], Event::Fill {taker_id, maker_id, taker_client_id: None, maker_client_id: None, price: 10, qty: 10, ts});
+        assert_eq!(result.events[1], Event::Done {id: taker_id, client_id: None, reason: DoneReason::Filled, remaining: 0, ts});
+        // Fully filled taker must not rest on the bid side
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.best_ask(), Some((10, 90)));
     }
 
     #[test]
-    fn test_market_order_fill_events() {
-        let now = Instant::now();
-        let ts = now.elapsed().as_secs(); 
+    fn test_limit_order_sweeps_levels_and_rests_remainder() {
         let mut book = Book::new();
-        let req1 = OrderRequest {side: Side::SELL, price: Some(10), quantity: 100 };
-        let (maker_id, _) = book.submit(&req1);
-        let req2 = OrderRequest {side: Side::BUY, price: None, quantity: 10};
+        let (m1, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        let (m2, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(11), quantity: 5, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(13), quantity: 5, client_id: None });
+        let (taker_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(12), quantity: 12, client_id: None });
+
+        assert_eq!(result.events.len(), 5);
+        assert!(matches!(result.events[0], Event::Fill { maker_id, price: 10, qty: 5, .. } if maker_id == m1));
+        assert!(matches!(result.events[1], Event::Done { id, reason: DoneReason::Filled, .. } if id == m1));
+        assert!(matches!(result.events[2], Event::Fill { maker_id, price: 11, qty: 5, .. } if maker_id == m2));
+        assert!(matches!(result.events[3], Event::Done { id, reason: DoneReason::Filled, .. } if id == m2));
+        assert!(matches!(result.events[4], Event::Done { id, reason: DoneReason::Rested, .. } if id == taker_id));
+
+        // Exhausted makers and their levels are gone, remainder rests at the limit
+        assert!(!book.id_index.contains_key(&m1));
+        assert!(!book.id_index.contains_key(&m2));
+        assert_eq!(book.best_ask(), Some((13, 5)));
+        assert_eq!(book.best_bid(), Some((12, 2)));
+        assert!(book.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_partial_fill_then_rest_reconciles() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(100), quantity: 30, client_id: None });
+        let (taker_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(100), quantity: 100, client_id: None });
+
+        let filled: u64 = result.events.iter().map(|e| match e {
+            Event::Fill { qty, .. } => *qty,
+            _ => 0,
+        }).sum();
+        let rested = match result.events.last() {
+            Some(Event::Done { id, reason: DoneReason::Rested, remaining, .. }) if *id == taker_id => *remaining,
+            other => panic!("Expected Done Rested for taker, got {other:?}"),
+        };
+        assert_eq!(filled, 30);
+        assert_eq!(rested, 70);
+        assert_eq!(filled + rested, 100);
+        assert_eq!(book.best_bid(), Some((100, 70)));
+    }
+
+    #[test]
+    #[should_panic(expected = "book invariant violated")]
+    fn test_inconsistent_book_trips_invariant() {
+        let mut book = Book::new();
+        // A resting bid with no id_index entry
+        let mut level = Level::new();
+        level.push_back(Resting { id: OrderId(42), price: Some(100), quantity: 10, ts: 0, remaining: 10, active: true, client_id: None });
+        book.bids.insert(100, level);
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(200), quantity: 1, client_id: None });
+    }
+
+    #[test]
+    fn test_market_order_fill_events() {
+        let mut book = fixed_clock_book();
+        let ts = book.now();
+        let req1 = OrderRequest {side: Side::SELL, price: Some(10), quantity: 100, client_id: None };
+        let (maker_id, _) = book.submit(&req1);
+        let req2 = OrderRequest {side: Side::BUY, price: None, quantity: 10, client_id: None};
         let (taker_id, result) = book.submit(&req2);
         assert_eq!(result.events.len(), 2);
-        assert_eq!(result.events[0], Event::Fill {taker_id, maker_id, price: 10, qty: 10, ts});
-        assert_eq!(result.events[1], Event::Done {id: taker_id, reason: DoneReason::Filled, ts});
+        assert_eq!(result.events[0], Event::Fill {taker_id, maker_id, taker_client_id: None, maker_client_id: None, price: 10, qty: 10, ts});
+        assert_eq!(result.events[1], Event::Done {id: taker_id, client_id: None, reason: DoneReason::Filled, remaining: 0, ts});
+    }
+
+    #[test]
+    fn test_with_start_id() {
+        let mut book = Book::with_start_id(500);
+        let (first, _) = book.submit(&OrderRequest { side: Side::BUY, price: Some(100), quantity: 10, client_id: None });
+        let (second, _) = book.submit(&OrderRequest { side: Side::SELL, price: Some(105), quantity: 5, client_id: None });
+        assert_eq!(first, OrderId(500));
+        assert_eq!(second, OrderId(501));
+        assert_eq!(book.id_index.get(&OrderId(500)), Some(&(Side::BUY, 100)));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut book = Book::new();
+        assert_eq!(book.next_order_id(), 1);
+        book.submit(&OrderRequest { side: Side::BUY, price: Some(99), quantity: 10, client_id: None });
+        book.submit(&OrderRequest { side: Side::BUY, price: Some(99), quantity: 5, client_id: None });
+        book.submit(&OrderRequest { side: Side::BUY, price: Some(98), quantity: 5, client_id: None });
+        book.submit(&OrderRequest { side: Side::SELL, price: Some(101), quantity: 7, client_id: None });
+        // Fully consumes the 101 ask, so it no longer counts
+        book.submit(&OrderRequest { side: Side::BUY, price: None, quantity: 7, client_id: None });
+
+        assert_eq!(book.stats(), BookStats { bid_levels: 2, ask_levels: 0, total_orders: 3, next_order_id: 6 });
+        assert_eq!(book.next_order_id(), 6);
+    }
+
+    #[test]
+    fn test_market_order_residual_is_partially_filled_then_cancelled() {
+        let mut book = fixed_clock_book();
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 4, client_id: None });
+        let (taker_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 10, client_id: None });
+        assert_eq!(result.events.len(), 3);
+        assert_eq!(result.events[2], Event::Done {id: taker_id, client_id: None, reason: DoneReason::PartiallyFilledThenCancelled, remaining: 6, ts: 0});
+
+        // Nothing to fill against at all is still a plain rejection
+        let (taker_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 10, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id: taker_id, client_id: None, reason: DoneReason::Rejected(RejectReason::NoLiquidity), remaining: 10, ts: 0}]);
+    }
+
+    // Golden matching results. CI runs this with and without the `hot-path`
+    // feature so stripping instrumentation can never change what the engine does.
+    #[test]
+    fn test_matching_results_independent_of_instrumentation() {
+        let mut book = fixed_clock_book();
+        let reqs = [
+            OrderRequest {side: Side::SELL, price: Some(101), quantity: 5, client_id: None },
+            OrderRequest {side: Side::SELL, price: Some(102), quantity: 5, client_id: None },
+            OrderRequest {side: Side::BUY, price: Some(99), quantity: 8, client_id: None },
+            OrderRequest {side: Side::BUY, price: Some(102), quantity: 7, client_id: None },
+            OrderRequest {side: Side::SELL, price: None, quantity: 10, client_id: None },
+        ];
+        let events: Vec<Vec<Event>> = reqs.iter().map(|r| book.submit(r).1.events).collect();
+
+        assert_eq!(events[3], vec![
+            Event::Fill {taker_id: OrderId(4), maker_id: OrderId(1), taker_client_id: None, maker_client_id: None, price: 101, qty: 5, ts: 0},
+            Event::Done {id: OrderId(1), client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+            Event::Fill {taker_id: OrderId(4), maker_id: OrderId(2), taker_client_id: None, maker_client_id: None, price: 102, qty: 2, ts: 0},
+            Event::Done {id: OrderId(4), client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+        ]);
+        assert_eq!(events[4], vec![
+            Event::Fill {taker_id: OrderId(5), maker_id: OrderId(3), taker_client_id: None, maker_client_id: None, price: 99, qty: 8, ts: 0},
+            Event::Done {id: OrderId(3), client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+            Event::Done {id: OrderId(5), client_id: None, reason: DoneReason::PartiallyFilledThenCancelled, remaining: 2, ts: 0},
+        ]);
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.best_ask(), Some((102, 3)));
+    }
+
+    #[test]
+    fn test_fully_consumed_maker_gets_done() {
+        let mut book = fixed_clock_book();
+        let (m1, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        let (m2, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        let (taker_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 7, client_id: None });
+
+        assert_eq!(result.events, vec![
+            Event::Fill {taker_id, maker_id: m1, taker_client_id: None, maker_client_id: None, price: 10, qty: 5, ts: 0},
+            Event::Done {id: m1, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+            Event::Fill {taker_id, maker_id: m2, taker_client_id: None, maker_client_id: None, price: 10, qty: 2, ts: 0},
+            Event::Done {id: taker_id, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+        ]);
+        // Partially filled maker stays on the book with no Done
+        assert_eq!(book.best_ask(), Some((10, 3)));
+    }
+
+    #[test]
+    fn test_coalesced_fills_merge_per_price() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut book = fixed_clock_book();
+        book.set_coalesce_fills(true);
+        let (m1, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 3, client_id: Some(1) });
+        let (m2, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 4, client_id: None });
+        let (m3, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        let (m4, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(11), quantity: 5, client_id: None });
+        book.set_observer(Box::new(RecordingObserver(seen.clone())));
+        let (taker_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(11), quantity: 14, client_id: None });
+
+        assert_eq!(result.events, vec![
+            Event::Fill {taker_id, maker_id: m1, taker_client_id: None, maker_client_id: Some(1), price: 10, qty: 12, ts: 0},
+            Event::Done {id: m1, client_id: Some(1), reason: DoneReason::Filled, remaining: 0, ts: 0},
+            Event::Done {id: m2, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+            Event::Done {id: m3, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+            Event::Fill {taker_id, maker_id: m4, taker_client_id: None, maker_client_id: None, price: 11, qty: 2, ts: 0},
+            Event::Done {id: taker_id, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+        ]);
+        assert_eq!((result.fill_count, result.filled_qty), (2, 14));
+        assert_eq!(book.best_ask(), Some((11, 3)));
+        assert!(book.check_invariants().is_ok());
+
+        // Observers still get one Fill per maker, each followed by its Done
+        let fill = |maker_id, maker_client_id, price, qty| format!("{:?}", Event::Fill {taker_id, maker_id, taker_client_id: None, maker_client_id, price, qty, ts: 0});
+        let done = |id, client_id| format!("{:?}", Event::Done {id, client_id, reason: DoneReason::Filled, remaining: 0, ts: 0});
+        assert_eq!(*seen.lock().unwrap(), vec![
+            fill(m1, Some(1), 10, 3), done(m1, Some(1)),
+            fill(m2, None, 10, 4), done(m2, None),
+            fill(m3, None, 10, 5), done(m3, None),
+            fill(m4, None, 11, 2), done(taker_id, None),
+        ]);
+
+        // Batches merge only what they hand back, after observers have seen it
+        seen.lock().unwrap().clear();
+        let (m5, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(11), quantity: 1, client_id: None });
+        let results = book.submit_batch(&[OrderRequest {side: Side::BUY, price: Some(11), quantity: 4, client_id: None }], true);
+        let (batch_taker, batch) = &results[0];
+        assert_eq!((batch.fill_count, batch.filled_qty), (1, 4));
+        assert!(matches!(batch.events[0], Event::Fill {maker_id, qty: 4, ..} if maker_id == m4));
+        let fills_seen = seen.lock().unwrap().iter().filter(|e| e.starts_with("Fill")).count();
+        assert_eq!(fills_seen, 2);
+        assert!(!book.id_index.contains_key(&m5) && !book.id_index.contains_key(&m4) && !book.id_index.contains_key(batch_taker));
+    }
+
+    #[test]
+    fn test_atomic_batch_rolls_back_on_reject() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(101), quantity: 5, client_id: None });
+        let before_bids = book.bids.clone();
+        let before_asks = book.asks.clone();
+
+        let batch = [
+            OrderRequest {side: Side::BUY, price: Some(99), quantity: 5, client_id: Some(1) },
+            OrderRequest {side: Side::BUY, price: Some(101), quantity: 2, client_id: Some(2) },
+            OrderRequest {side: Side::SELL, price: Some(102), quantity: 0, client_id: Some(3) },
+        ];
+        let results = book.submit_batch(&batch, true);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().zip(&batch).all(|((_, r), req)| {
+            r.fill_count == 0 && r.events.len() == 1
+                && matches!(r.events[0], Event::Done {client_id, remaining, ..} if client_id == req.client_id && remaining == req.quantity)
+        }));
+        assert_eq!(results.iter().map(|(_, r)| r.terminal_reason).collect::<Vec<_>>(), vec![
+            Some(DoneReason::Rejected(RejectReason::BatchRolledBack)),
+            Some(DoneReason::Rejected(RejectReason::BatchRolledBack)),
+            Some(DoneReason::Rejected(RejectReason::InvalidQuantity)),
+        ]);
+        assert_eq!((book.bids.clone(), book.asks.clone()), (before_bids, before_asks));
+        assert_eq!(book.last_price(), None);
+        assert_eq!(book.stats().total_orders, 1);
+
+        // Without the bad order the same batch applies in full
+        let results = book.submit_batch(&batch[..2], true);
+        assert_eq!(results.iter().map(|(_, r)| r.terminal_reason).collect::<Vec<_>>(), vec![Some(DoneReason::Rested), Some(DoneReason::Filled)]);
+        assert_eq!(book.best_bid(), Some((99, 5)));
+        assert_eq!(book.best_ask(), Some((101, 3)));
+
+        // Non-atomic keeps the good orders either side of a rejection
+        let results = book.submit_batch(&batch, false);
+        assert_eq!(results[2].1.terminal_reason, Some(DoneReason::Rejected(RejectReason::InvalidQuantity)));
+        assert_eq!(book.best_bid(), Some((99, 10)));
+        assert_eq!(book.best_ask(), Some((101, 1)));
+    }
+
+    #[test]
+    fn test_external_fill_consumes_best_quote() {
+        let mut book = Book::new();
+        let (best, _) = book.submit(&OrderRequest {side: Side::BUY, price: Some(100), quantity: 5, client_id: Some(4) });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(99), quantity: 5, client_id: None });
+
+        let events = book.apply_external_fill(Side::SELL, 100, 3, 7);
+        let taker_id = OrderId::EXTERNAL;
+        assert_eq!(events, vec![Event::Fill {taker_id, maker_id: best, taker_client_id: None, maker_client_id: Some(4), price: 100, qty: 3, ts: 7}]);
+        // The external side takes no id from the book
+        assert_eq!(book.next_order_id(), 3);
+        assert_eq!(book.best_bid(), Some((100, 2)));
+        assert_eq!(book.last_price(), Some(100));
+
+        // Only our orders at or better than the external price are touched
+        let events = book.apply_external_fill(Side::SELL, 100, 10, 8);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[1], Event::Done {id, reason: DoneReason::Filled, ..} if id == best));
+        assert_eq!(book.best_bid(), Some((99, 5)));
+        assert_eq!(book.queue_ahead(best), None);
+        assert!(book.apply_external_fill(Side::BUY, 200, 5, 9).is_empty());
+    }
+
+    #[test]
+    fn test_get_order_after_partial_fill() {
+        let clock = SimClock::new(100, 1);
+        let mut book = Book::new_for_sim(Box::new(clock));
+        let (id, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(50), quantity: 10, client_id: Some(8) });
+        book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 4, client_id: None });
+
+        assert_eq!(book.get_order(id), Some(ResolvedOrder {
+            id, client_id: Some(8), side: Side::SELL, price: 50, quantity: 10, remaining: 6, ts: 100,
+        }));
+        assert_eq!(book.get_order(OrderId(2)), None);
+        book.cancel(id, 0);
+        assert_eq!(book.get_order(id), None);
+    }
+
+    #[test]
+    fn test_cumulative_cost() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(100), quantity: 5, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(101), quantity: 5, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(103), quantity: 10, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(98), quantity: 4, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(97), quantity: 4, client_id: None });
+
+        // 5@100 + 5@101 + 2@103
+        assert_eq!(book.cumulative_cost(Side::BUY, 12), Some((1211, 103)));
+        assert_eq!(book.cumulative_cost(Side::BUY, 5), Some((500, 100)));
+        // 4@98 + 2@97
+        assert_eq!(book.cumulative_cost(Side::SELL, 6), Some((586, 97)));
+
+        // Not enough liquidity to fill the full size
+        assert_eq!(book.cumulative_cost(Side::BUY, 21), None);
+        assert_eq!(book.cumulative_cost(Side::SELL, 9), None);
+        assert_eq!(Book::new().cumulative_cost(Side::BUY, 1), None);
+    }
+
+    struct RecordingObserver(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl EventObserver for RecordingObserver {
+        fn on_event(&mut self, event: &Event) {
+            self.0.lock().unwrap().push(format!("{event:?}"));
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_every_event_in_order() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut book = Book::new();
+        book.set_observer(Box::new(RecordingObserver(seen.clone())));
+
+        let (maker_id, rest) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        let (_, cross) = book.submit(&OrderRequest {side: Side::BUY, price: Some(11), quantity: 8, client_id: None });
+        let cancel = book.cancel_limit_order(Order {id: maker_id, side: Side::SELL, price: Some(10), quantity: 5, client_id: None }, 0);
+        assert!(cancel.is_none());
+
+        let expected: Vec<String> = rest.events.iter().chain(cross.events.iter())
+            .map(|e| format!("{e:?}"))
+            .collect();
+        assert_eq!(cross.events.len(), 3);
+        assert_eq!(*seen.lock().unwrap(), expected);
+
+        // Once removed the observer stops receiving events
+        assert!(book.take_observer().is_some());
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(20), quantity: 1, client_id: None });
+        assert_eq!(seen.lock().unwrap().len(), expected.len());
+    }
+
+    #[test]
+    fn test_last_price_tracks_fills() {
+        let mut book = Book::new();
+        assert_eq!(book.last_price(), None);
+
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(100), quantity: 5, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(102), quantity: 5, client_id: None });
+        // Resting alone doesn't trade
+        assert_eq!(book.last_price(), None);
+
+        // Sweeps both levels, last print is the deeper one
+        book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 7, client_id: None });
+        assert_eq!(book.last_price(), Some(102));
+
+        // A rejected market order leaves it unchanged
+        book.submit(&OrderRequest {side: Side::SELL, price: None, quantity: 1, client_id: None });
+        assert_eq!(book.last_price(), Some(102));
+    }
+
+    #[test]
+    fn test_halt_rejects_aggressive_orders_but_allows_cancels() {
+        let mut book = fixed_clock_book();
+        let (ask_id, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        book.set_halted(true);
+        assert!(book.is_halted());
+
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 2, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::Halted), remaining: 2, ts: 0}]);
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 2, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::Halted), remaining: 2, ts: 0}]);
+        assert_eq!(book.best_ask(), Some((10, 5)));
+
+        // Passive orders can still rest under the default policy
+        let (bid_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(9), quantity: 2, client_id: None });
+        assert!(matches!(result.events[0], Event::Done { reason: DoneReason::Rested, .. }));
+
+        // Participants can pull quotes during the halt
+        let cancel = book.cancel_limit_order(Order {id: ask_id, side: Side::SELL, price: Some(10), quantity: 5, client_id: None }, 0);
+        assert!(cancel.is_some());
+
+        book.set_halt_policy(HaltPolicy::AllOrders);
+        let (_, result) = book.submit(&OrderRequest {side: Side::SELL, price: Some(20), quantity: 1, client_id: None });
+        assert!(matches!(result.events[0], Event::Done { reason: DoneReason::Rejected(RejectReason::Halted), .. }));
+
+        // Normal operation resumes after un-halting
+        book.set_halted(false);
+        let (taker_id, result) = book.submit(&OrderRequest {side: Side::SELL, price: None, quantity: 2, client_id: None });
+        assert_eq!(result.events, vec![
+            Event::Fill {taker_id, maker_id: bid_id, taker_client_id: None, maker_client_id: None, price: 9, qty: 2, ts: 0},
+            Event::Done {id: bid_id, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+            Event::Done {id: taker_id, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0},
+        ]);
+    }
+
+    #[test]
+    fn test_level_cap_evicts_furthest_level() {
+        let mut book = fixed_clock_book();
+        book.set_max_levels_per_side(Some(2));
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(100), quantity: 1, client_id: None });
+        let (deep_a, _) = book.submit(&OrderRequest {side: Side::BUY, price: Some(98), quantity: 2, client_id: None });
+        let (deep_b, _) = book.submit(&OrderRequest {side: Side::BUY, price: Some(98), quantity: 3, client_id: None });
+
+        // A third level pushes the 98 level out
+        let (new_id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(99), quantity: 4, client_id: None });
+        assert_eq!(result.events, vec![
+            Event::Done {id: new_id, client_id: None, reason: DoneReason::Rested, remaining: 4, ts: 0},
+            Event::Done {id: deep_a, client_id: None, reason: DoneReason::Cancelled, remaining: 2, ts: 0},
+            Event::Done {id: deep_b, client_id: None, reason: DoneReason::Cancelled, remaining: 3, ts: 0},
+        ]);
+        assert_eq!(book.bids.keys().copied().collect::<Vec<_>>(), vec![99, 100]);
+        assert!(!book.id_index.contains_key(&deep_a));
+
+        // Asks evict from the top
+        book.set_max_levels_per_side(Some(1));
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(105), quantity: 1, client_id: None });
+        let (far_ask, result) = book.submit(&OrderRequest {side: Side::SELL, price: Some(110), quantity: 1, client_id: None });
+        assert_eq!(result.events[1], Event::Done {id: far_ask, client_id: None, reason: DoneReason::Cancelled, remaining: 1, ts: 0});
+        assert_eq!(book.best_ask(), Some((105, 1)));
+        assert!(book.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_orders_in_range() {
+        let mut book = Book::new();
+        for (price, qty) in [(95, 1), (97, 2), (97, 3), (99, 4), (100, 5)] {
+            book.submit(&OrderRequest {side: Side::BUY, price: Some(price), quantity: qty, client_id: None });
+        }
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(98), quantity: 1, client_id: None });
+
+        // Inclusive at both ends; 95 and 100 are outside. The sell took 1 from the 100 bid.
+        assert_eq!(book.orders_in_range(Side::BUY, 96, 99), vec![(97, 5), (99, 4)]);
+        assert_eq!(book.orders_in_range(Side::BUY, 100, 100), vec![(100, 4)]);
+        assert_eq!(book.orders_in_range(Side::SELL, 0, 1_000), vec![]);
+        assert_eq!(book.orders_in_range(Side::BUY, 99, 96), vec![]);
+    }
+
+    #[test]
+    fn test_resting_notional() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(99), quantity: 10, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(98), quantity: 5, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(98), quantity: 1, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(101), quantity: 3, client_id: None });
+
+        assert_eq!(book.resting_notional(Side::BUY), 99 * 10 + 98 * 6);
+        assert_eq!(book.resting_notional(Side::SELL), 101 * 3);
+
+        // Partial fills reduce it; a large level doesn't overflow
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(99), quantity: 4, client_id: None });
+        assert_eq!(book.resting_notional(Side::BUY), 99 * 6 + 98 * 6);
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(i64::MAX), quantity: u64::MAX, client_id: None });
+        assert_eq!(book.resting_notional(Side::SELL), 101 * 3 + i64::MAX as u128 * u64::MAX as u128);
+        assert_eq!(Book::new().resting_notional(Side::SELL), 0);
+
+        // Negative prices count by magnitude
+        let mut book = Book::new();
+        book.set_allow_non_positive_prices(true);
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(-5), quantity: 4, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(0), quantity: 7, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(i64::MIN), quantity: 1, client_id: None });
+        assert_eq!(book.resting_notional(Side::BUY), 5 * 4 + i64::MIN.unsigned_abs() as u128);
+    }
+
+    #[test]
+    fn test_level_totals_track_random_ops() {
+        let mut book = Book::new();
+        // Small LCG so the sequence is the same on every run
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        let mut ids = vec![];
+
+        for _ in 0..2_000 {
+            let side = if next(2) == 0 { Side::BUY } else { Side::SELL };
+            match next(10) {
+                0..=5 => {
+                    let price = Some(95 + next(11) as i64);
+                    let (id, _) = book.submit(&OrderRequest {side, price, quantity: 1 + next(20), client_id: None });
+                    ids.push(id);
+                }
+                6 => {
+                    book.submit(&OrderRequest {side, price: None, quantity: 1 + next(30), client_id: None });
+                }
+                7 | 8 if !ids.is_empty() => {
+                    let id = ids.swap_remove(next(ids.len() as u64) as usize);
+                    book.cancel(id, 0);
+                }
+                _ if !ids.is_empty() => {
+                    let id = ids[next(ids.len() as u64) as usize];
+                    if let Some(&(side, price)) = book.id_index.get(&id) {
+                        book.cancel_replace(id, &OrderRequest {side, price: Some(price), quantity: 1 + next(5), client_id: None });
+                    }
+                }
+                _ => {}
+            }
+
+            for level in book.bids.values().chain(book.asks.values()) {
+                let live: u128 = level.iter().filter(|r| r.active).map(|r| r.remaining as u128).sum();
+                assert_eq!(level.total_remaining, live);
+            }
+        }
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let mut book = fixed_clock_book();
+        assert!(matches!(book.set_size_limits(101, 100), Err(BookError::InvalidSizeLimits { min: 101, max: 100 })));
+        book.set_size_limits(5, 100).unwrap();
+
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 4, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::SizeOutOfBounds), remaining: 4, ts: 0}]);
+        let (id, result) = book.submit(&OrderRequest {side: Side::SELL, price: None, quantity: 101, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::SizeOutOfBounds), remaining: 101, ts: 0}]);
+        // Zero is still rejected as an invalid quantity
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 0, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::InvalidQuantity), remaining: 0, ts: 0}]);
+        assert!(book.id_index.is_empty());
+
+        // Both bounds are inclusive, and a refused update keeps them
+        assert!(book.set_size_limits(7, 6).is_err());
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 5, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rested, remaining: 5, ts: 0}]);
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 100, client_id: None });
+        assert_eq!(book.best_bid(), Some((10, 105)));
+    }
+
+    #[test]
+    fn test_non_positive_limit_price_rejected() {
+        let mut book = fixed_clock_book();
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(0), quantity: 3, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::InvalidPrice), remaining: 3, ts: 0}]);
+        let (id, result) = book.submit(&OrderRequest {side: Side::SELL, price: Some(-5), quantity: 3, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::InvalidPrice), remaining: 3, ts: 0}]);
+        assert!(book.id_index.is_empty());
+
+        // Market orders have no price and are unaffected
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 3, client_id: None });
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 3, client_id: None });
+        assert_eq!(result.events.last(), Some(&Event::Done {id, client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0}));
+
+        book.set_allow_non_positive_prices(true);
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(0), quantity: 3, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(-2), quantity: 4, client_id: None });
+        assert_eq!(book.best_ask(), Some((0, 3)));
+        assert_eq!(book.best_bid(), Some((-2, 4)));
+    }
+
+    #[test]
+    fn test_submit_result_summary() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 2, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 3, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(11), quantity: 4, client_id: None });
+
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(11), quantity: 12, client_id: None });
+        let fills: Vec<u64> = result.events.iter()
+            .filter_map(|e| match e { Event::Fill {taker_id, qty, ..} if *taker_id == id => Some(*qty), _ => None })
+            .collect();
+        assert_eq!(result.fill_count, fills.len());
+        assert_eq!(result.fill_count, 3);
+        assert_eq!(result.filled_qty, fills.iter().sum::<u64>());
+        assert_eq!(result.filled_qty, 9);
+        assert_eq!(result.resting_qty, 3);
+        // The makers' Done events don't count as this order's terminal reason
+        assert_eq!(result.terminal_reason, Some(DoneReason::Rested));
+
+        let cancelled = book.cancel(id, 0).unwrap();
+        assert_eq!((cancelled.fill_count, cancelled.resting_qty, cancelled.terminal_reason), (0, 0, Some(DoneReason::Cancelled)));
+    }
+
+    #[test]
+    fn test_sim_book_matches_normal_book() {
+        fn without_ts(events: &[Event]) -> Vec<Event> {
+            events.iter().map(|e| match *e {
+                Event::Ack {id, client_id, ..} => Event::Ack {id, client_id, ts: 0},
+                Event::Fill {taker_id, maker_id, taker_client_id, maker_client_id, price, qty, ..} =>
+                    Event::Fill {taker_id, maker_id, taker_client_id, maker_client_id, price, qty, ts: 0},
+                Event::Done {id, client_id, reason, remaining, ..} => Event::Done {id, client_id, reason, remaining, ts: 0},
+            }).collect()
+        }
+        fn run(book: &mut Book) -> Vec<Event> {
+            let mut events = vec![];
+            for (side, price, quantity) in [(Side::SELL, Some(101), 5), (Side::SELL, Some(102), 5), (Side::BUY, Some(99), 4), (Side::BUY, Some(102), 7), (Side::SELL, None, 6)] {
+                events.extend(book.submit(&OrderRequest {side, price, quantity, client_id: None }).1.events);
+            }
+            events.extend(book.cancel_replace(OrderId(2), &OrderRequest {side: Side::SELL, price: Some(102), quantity: 1, client_id: None }).1.events);
+            events
+        }
+
+        let mut normal = Book::new();
+        let mut sim = Book::new_for_sim(Box::new(SimClock::new(1_000, 10)));
+        let normal_events = run(&mut normal);
+        let sim_events = run(&mut sim);
+        assert_eq!(without_ts(&normal_events), without_ts(&sim_events));
+        for side in [Side::BUY, Side::SELL] {
+            assert_eq!(normal.orders_in_range(side, i64::MIN, i64::MAX), sim.orders_in_range(side, i64::MIN, i64::MAX));
+        }
+        assert_eq!(normal.stats(), sim.stats());
+
+        // Timestamps follow the injected clock, one reading per operation
+        let ts: Vec<u64> = sim_events.iter().map(|e| match e { Event::Ack {ts, ..} | Event::Fill {ts, ..} | Event::Done {ts, ..} => *ts }).collect();
+        assert_eq!(ts.first(), Some(&1_000));
+        assert_eq!(ts.last(), Some(&1_050));
+        let mut again = Book::new_for_sim(Box::new(SimClock::new(1_000, 10)));
+        assert_eq!(run(&mut again), sim_events);
+    }
+
+    #[test]
+    fn test_notional_ceiling() {
+        let mut book = fixed_clock_book();
+        book.set_max_notional(Some(u64::MAX as u128));
+
+        // Exactly at the ceiling is fine; one lot more would overflow a u64 cost
+        let (_, result) = book.submit(&OrderRequest {side: Side::SELL, price: Some(1), quantity: u64::MAX, client_id: None });
+        assert_eq!(result.terminal_reason, Some(DoneReason::Rested));
+        let (id, result) = book.submit(&OrderRequest {side: Side::SELL, price: Some(2), quantity: u64::MAX / 2 + 1, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::NotionalTooLarge), remaining: u64::MAX / 2 + 1, ts: 0}]);
+        let (_, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(i64::MAX), quantity: u64::MAX, client_id: None });
+        assert_eq!(result.terminal_reason, Some(DoneReason::Rejected(RejectReason::NotionalTooLarge)));
+        assert_eq!(book.stats().total_orders, 1);
+
+        // Market orders have no price to check
+        let (_, result) = book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 5, client_id: None });
+        assert_eq!(result.terminal_reason, Some(DoneReason::Filled));
+    }
+
+    #[test]
+    fn test_fill_ordering_across_levels() {
+        let mut book = Book::new();
+        // Makers submitted out of price order; ids 1..=5
+        for (price, qty) in [(102, 2), (101, 1), (101, 2), (103, 5), (102, 3)] {
+            book.submit(&OrderRequest {side: Side::SELL, price: Some(price), quantity: qty, client_id: None });
+        }
+        let (taker, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(103), quantity: 10, client_id: None });
+        let fills: Vec<(u64, i64, u64)> = result.events.iter()
+            .filter_map(|e| match e { Event::Fill {maker_id, price, qty, ..} => Some((maker_id.0, *price, *qty)), _ => None })
+            .collect();
+        // Best price first, FIFO within 101 and 102
+        assert_eq!(fills, vec![(2, 101, 1), (3, 101, 2), (1, 102, 2), (5, 102, 3), (4, 103, 2)]);
+        assert!(fills.windows(2).all(|w| w[0].1 <= w[1].1));
+
+        // Each emptied maker's Done directly follows its last Fill; the taker's Done is last
+        for (i, event) in result.events.iter().enumerate() {
+            if let Event::Done {id, reason: DoneReason::Filled, ..} = event {
+                if *id != taker {
+                    assert!(matches!(result.events[i - 1], Event::Fill {maker_id, ..} if maker_id == *id));
+                }
+            }
+        }
+        assert!(matches!(result.events.last(), Some(Event::Done {id, reason: DoneReason::Filled, ..}) if *id == taker));
+
+        // Sells walk bids from the highest price down
+        for price in [97, 99, 98] {
+            book.submit(&OrderRequest {side: Side::BUY, price: Some(price), quantity: 1, client_id: None });
+        }
+        let (_, result) = book.submit(&OrderRequest {side: Side::SELL, price: None, quantity: 3, client_id: None });
+        let prices: Vec<i64> = result.events.iter().filter_map(|e| match e { Event::Fill {price, ..} => Some(*price), _ => None }).collect();
+        assert_eq!(prices, vec![99, 98, 97]);
+    }
+
+    #[test]
+    fn test_aggregate_overflow_saturate() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: u64::MAX, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: None });
+        assert_eq!(book.best_ask(), Some((10, u64::MAX)));
+        assert_eq!(book.orders_in_range(Side::SELL, 10, 10), vec![(10, u64::MAX)]);
+
+        // The cached total stays exact underneath, so it comes back into range
+        book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 5, client_id: None });
+        assert_eq!(book.best_ask(), Some((10, u64::MAX)));
+        book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 1, client_id: None });
+        assert_eq!(book.best_ask(), Some((10, u64::MAX - 1)));
+    }
+
+    #[test]
+    fn test_aggregate_overflow_error() {
+        let mut book = fixed_clock_book();
+        book.set_aggregate_overflow(AggregateOverflow::Error);
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: u64::MAX - 1, client_id: None });
+        // Exactly u64::MAX is still representable
+        let (_, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 1, client_id: None });
+        assert_eq!(result.terminal_reason, Some(DoneReason::Rested));
+        let (id, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 1, client_id: None });
+        assert_eq!(result.events, vec![Event::Done {id, client_id: None, reason: DoneReason::Rejected(RejectReason::AggregateOverflow), remaining: 1, ts: 0}]);
+        assert_eq!(book.best_bid(), Some((10, u64::MAX)));
+        // Other levels are unaffected
+        let (_, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(9), quantity: 1, client_id: None });
+        assert_eq!(result.terminal_reason, Some(DoneReason::Rested));
+    }
+
+    #[test]
+    #[should_panic(expected = "aggregate size overflow")]
+    fn test_aggregate_overflow_panic() {
+        let mut book = Book::new();
+        book.set_aggregate_overflow(AggregateOverflow::Panic);
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: u64::MAX, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 1, client_id: None });
+    }
+
+    #[test]
+    fn test_aggregate_totals_saturate() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(i64::MAX), quantity: u64::MAX, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(i64::MAX), quantity: u64::MAX, client_id: None });
+        let (last, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(i64::MAX), quantity: u64::MAX, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(i64::MAX - 1), quantity: u64::MAX, client_id: None });
+
+        assert_eq!(book.cumulative_depth(Side::SELL, 2), vec![(i64::MAX - 1, u64::MAX), (i64::MAX, u64::MAX)]);
+        assert_eq!(book.queue_ahead(last), Some(u64::MAX));
+        assert_eq!(book.resting_notional(Side::SELL), u128::MAX);
+
+        // Error can't reject a read, so it clamps too
+        book.set_aggregate_overflow(AggregateOverflow::Error);
+        assert_eq!(book.queue_ahead(last), Some(u64::MAX));
+    }
+
+    #[test]
+    #[should_panic(expected = "aggregate size overflow in cumulative depth")]
+    fn test_aggregate_totals_panic() {
+        let mut book = Book::new();
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: u64::MAX, client_id: None });
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(9), quantity: 1, client_id: None });
+        book.set_aggregate_overflow(AggregateOverflow::Panic);
+        book.cumulative_depth(Side::BUY, 2);
+    }
+
+    #[test]
+    fn test_cumulative_depth() {
+        let mut book = Book::new();
+        assert!(book.cumulative_depth(Side::SELL, 5).is_empty());
+        for (price, qty) in [(103, 2), (101, 4), (102, 1), (101, 3), (105, 6)] {
+            book.submit(&OrderRequest {side: Side::SELL, price: Some(price), quantity: qty, client_id: None });
+        }
+
+        let levels = book.depth(Side::SELL, 3);
+        let cumulative = book.cumulative_depth(Side::SELL, 3);
+        assert_eq!(cumulative, vec![(101, 7), (102, 8), (103, 10)]);
+        assert!(cumulative.windows(2).all(|w| w[0].1 < w[1].1));
+        assert_eq!(cumulative.last().map(|(_, total)| *total), Some(levels.iter().map(|(_, size)| size).sum()));
+        assert!(book.cumulative_depth(Side::BUY, 3).is_empty());
+    }
+
+    #[test]
+    fn test_depth_with_counts() {
+        let mut book = Book::new();
+        for (price, qty) in [(99, 1), (100, 2), (100, 3), (98, 4), (100, 5), (98, 1)] {
+            book.submit(&OrderRequest {side: Side::BUY, price: Some(price), quantity: qty, client_id: None });
+        }
+        let (cancelled, _) = book.submit(&OrderRequest {side: Side::BUY, price: Some(97), quantity: 1, client_id: None });
+        book.cancel(cancelled, 0);
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(101), quantity: 6, client_id: None });
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(101), quantity: 1, client_id: None });
+
+        assert_eq!(book.depth_with_counts(Side::BUY, 10), vec![(100, 10, 3), (99, 1, 1), (98, 5, 2)]);
+        assert_eq!(book.depth_with_counts(Side::BUY, 2), vec![(100, 10, 3), (99, 1, 1)]);
+        assert_eq!(book.depth(Side::BUY, 1), vec![(100, 10)]);
+        assert_eq!(book.depth_with_counts(Side::SELL, 5), vec![(101, 7, 2)]);
+
+        // A partial fill shrinks the size but not the count; a full one drops the order
+        book.submit(&OrderRequest {side: Side::SELL, price: Some(100), quantity: 4, client_id: None });
+        assert_eq!(book.depth_with_counts(Side::BUY, 1), vec![(100, 6, 2)]);
+        assert_eq!(book.depth_with_counts(Side::BUY, 0), vec![]);
+    }
+
+    #[test]
+    fn test_queue_ahead() {
+        let mut book = Book::new();
+        let (front, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 4, client_id: None });
+        let (middle, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 6, client_id: None });
+        let (back, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 1, client_id: None });
+
+        assert_eq!(book.queue_ahead(front), Some(0));
+        assert_eq!(book.queue_ahead(middle), Some(4));
+        assert_eq!(book.queue_ahead(back), Some(10));
+
+        // Fills at the front move everyone up
+        book.submit(&OrderRequest {side: Side::BUY, price: None, quantity: 3, client_id: None });
+        assert_eq!(book.queue_ahead(middle), Some(1));
+        book.cancel(front, 0);
+        assert_eq!(book.queue_ahead(back), Some(6));
+        assert_eq!(book.queue_ahead(front), None);
+        assert_eq!(book.queue_ahead(OrderId(999)), None);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut book = Book::new();
+        assert_eq!(book.checksum(10), 0);
+        for (side, price, qty) in [(Side::BUY, 100, 4), (Side::BUY, 100, 6), (Side::BUY, 99, 1), (Side::BUY, 98, 2), (Side::SELL, 101, 7)] {
+            book.submit(&OrderRequest {side, price: Some(price), quantity: qty, client_id: None });
+        }
+        // "100:10:101:7:99:1", the ask side running out after one level
+        assert_eq!(book.checksum(2), 4220672572);
+        // "100:10:101:7"
+        assert_eq!(book.checksum(1), 2683138128);
+
+        // Any change in the top levels changes it; changes below them don't
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(97), quantity: 1, client_id: None });
+        assert_eq!(book.checksum(2), 4220672572);
+        book.submit(&OrderRequest {side: Side::BUY, price: Some(99), quantity: 1, client_id: None });
+        assert_ne!(book.checksum(2), 4220672572);
+    }
+
+    #[test]
+    fn test_checksum_interleaves_levels() {
+        // Two levels a side: "33661:7:33668:9:33660:6:33680:8", expected CRC32
+        // computed independently with zlib
+        let mut book = Book::new();
+        for (side, price, qty) in [(Side::BUY, 33661, 7), (Side::BUY, 33660, 6), (Side::SELL, 33668, 9), (Side::SELL, 33680, 8)] {
+            book.submit(&OrderRequest {side, price: Some(price), quantity: qty, client_id: None });
+        }
+        assert_eq!(book.checksum(25), 3001390212);
+    }
+
+    #[test]
+    fn test_cancel_replace_reduces_in_place() {
+        let mut book = fixed_clock_book();
+        let (front, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 10, client_id: None });
+        let (back, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 10, client_id: None });
+
+        let (id, result) = book.cancel_replace(front, &OrderRequest {side: Side::SELL, price: Some(10), quantity: 4, client_id: None });
+        assert_eq!(id, front);
+        assert_eq!(result.events, vec![Event::Done {id: front, client_id: None, reason: DoneReason::Rested, remaining: 4, ts: 0}]);
+
+        // Still first in the queue
+        let queue = book.asks.get(&10).unwrap();
+        assert_eq!(queue.iter().map(|r| (r.id, r.remaining)).collect::<Vec<_>>(), vec![(front, 4), (back, 10)]);
+        assert_eq!(book.next_order_id(), 3);
+    }
+
+    #[test]
+    fn test_reduce_order() {
+        let mut book = Book::new();
+        let (front, _) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 10, client_id: None });
+        let (back, _) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 10, client_id: None });
+
+        let result = book.reduce_order(front, 6, 0).unwrap();
+        assert_eq!(result.events, vec![Event::Done {id: front, client_id: None, reason: DoneReason::Rested, remaining: 4, ts: 0}]);
+        let level = book.bids.get(&10).unwrap();
+        assert_eq!(level.iter().map(|r| (r.id, r.remaining, r.quantity)).collect::<Vec<_>>(), vec![(front, 4, 4), (back, 10, 10)]);
+        assert_eq!(book.best_bid(), Some((10, 14)));
+
+        // Reducing by more than what's left clamps to a full cancel
+        let result = book.reduce_order(front, 50, 0).unwrap();
+        assert_eq!(result.events, vec![Event::Done {id: front, client_id: None, reason: DoneReason::Cancelled, remaining: 4, ts: 0}]);
+        assert!(!book.id_index.contains_key(&front));
+        assert_eq!(book.reduce_order(front, 1, 0), None);
+
+        let result = book.reduce_order(back, 10, 0).unwrap();
+        assert_eq!(result.terminal_reason, Some(DoneReason::Cancelled));
+        assert_eq!(book.best_bid(), None);
+    }
+
+    #[test]
+    fn test_cancel_replace_full_replace() {
+        let mut book = fixed_clock_book();
+        let (front, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 10, client_id: None });
+        let (back, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 10, client_id: None });
+
+        // A size increase loses priority and gets a new id
+        let (new_id, result) = book.cancel_replace(front, &OrderRequest {side: Side::SELL, price: Some(10), quantity: 15, client_id: None });
+        assert_eq!(new_id, OrderId(3));
+        assert_eq!(result.events, vec![
+            Event::Done {id: front, client_id: None, reason: DoneReason::Cancelled, remaining: 10, ts: 0},
+            Event::Done {id: new_id, client_id: None, reason: DoneReason::Rested, remaining: 15, ts: 0},
+        ]);
+        let queue = book.asks.get(&10).unwrap();
+        assert_eq!(queue.iter().map(|r| r.id).collect::<Vec<_>>(), vec![back, new_id]);
+
+        // A price change is a full replace too
+        let (moved, _) = book.cancel_replace(back, &OrderRequest {side: Side::SELL, price: Some(11), quantity: 5, client_id: None });
+        assert_eq!(book.id_index.get(&moved), Some(&(Side::SELL, 11)));
+        assert!(!book.id_index.contains_key(&back));
+
+        // Unknown ids are rejected without submitting anything
+        let (id, result) = book.cancel_replace(OrderId(999), &OrderRequest {side: Side::SELL, price: Some(11), quantity: 5, client_id: None });
+        assert_eq!(id, OrderId(999));
+        assert_eq!(result.events, vec![Event::Done {id: OrderId(999), client_id: None, reason: DoneReason::Rejected(RejectReason::OrderNotFound), remaining: 5, ts: 0}]);
+        assert_eq!(book.next_order_id(), 5);
+    }
+
+    #[test]
+    fn test_cancel_replace_keeps_original_when_replacement_rejected() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut book = fixed_clock_book();
+        let (original, _) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 10, client_id: Some(1) });
+        book.set_observer(Box::new(RecordingObserver(seen.clone())));
+        book.set_halt_policy(HaltPolicy::AllOrders);
+        book.set_halted(true);
+
+        let (new_id, result) = book.cancel_replace(original, &OrderRequest {side: Side::BUY, price: Some(11), quantity: 10, client_id: Some(1) });
+        assert_ne!(new_id, original);
+        assert_eq!(result.events, vec![Event::Done {id: new_id, client_id: Some(1), reason: DoneReason::Rejected(RejectReason::Halted), remaining: 10, ts: 0}]);
+        assert_eq!(book.get_order(original).map(|o| (o.price, o.remaining)), Some((10, 10)));
+        assert_eq!(*seen.lock().unwrap(), vec![format!("{:?}", result.events[0])]);
+
+        // Unknown ids are reported to observers like any other rejection
+        book.set_halted(false);
+        let (_, result) = book.cancel_replace(OrderId(999), &OrderRequest {side: Side::BUY, price: Some(10), quantity: 1, client_id: None });
+        assert_eq!(seen.lock().unwrap().last(), Some(&format!("{:?}", result.events[0])));
+    }
+
+    #[test]
+    fn test_cancel_replace_in_place_respects_client_id_and_size_limits() {
+        let mut book = fixed_clock_book();
+        book.set_size_limits(5, 100).unwrap();
+        let (original, _) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 10, client_id: Some(1) });
+
+        // A reduction below min_qty is rejected and the original keeps its size
+        let (id, result) = book.cancel_replace(original, &OrderRequest {side: Side::SELL, price: Some(10), quantity: 4, client_id: Some(1) });
+        assert_ne!(id, original);
+        assert_eq!(result.terminal_reason, Some(DoneReason::Rejected(RejectReason::SizeOutOfBounds)));
+        assert_eq!(book.get_order(original).map(|o| o.remaining), Some(10));
+
+        // A new client id can't be amended in place, so it's a full replace
+        let (id, _) = book.cancel_replace(original, &OrderRequest {side: Side::SELL, price: Some(10), quantity: 6, client_id: Some(2) });
+        assert_ne!(id, original);
+        assert_eq!(book.get_order(original), None);
+        assert_eq!(book.get_order(id).map(|o| (o.client_id, o.remaining)), Some((Some(2), 6)));
+    }
+
+    #[test]
+    fn test_semantically_eq_ignores_residue() {
+        // Pinned clocks so both books stamp their orders alike
+        let mut a = fixed_clock_book();
+        let (cancelled, _) = a.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 1, client_id: None });
+        a.cancel(cancelled, 0);
+        a.submit(&OrderRequest {side: Side::SELL, price: Some(12), quantity: 3, client_id: None });
+        // Leftover inactive entry that a rebuilt book wouldn't have
+        a.asks.get_mut(&12).unwrap().push_back(Resting { id: OrderId(99), price: Some(12), quantity: 1, ts: 0, remaining: 1, active: false, client_id: None });
+
+        let mut b = Book::with_start_id(2);
+        b.clock = Some(Box::new(SimClock::new(0, 0)));
+        b.submit(&OrderRequest {side: Side::SELL, price: Some(12), quantity: 3, client_id: None });
+
+        // a still holds an empty bid level and the inactive entry
+        assert_ne!(a, b);
+        assert!(a.semantically_eq(&b));
+        assert!(b.semantically_eq(&a));
+
+        // Any difference in live state is caught
+        b.submit(&OrderRequest {side: Side::SELL, price: Some(12), quantity: 1, client_id: None });
+        assert!(!a.semantically_eq(&b));
+        assert!(!Book::new().semantically_eq(&Book::with_start_id(5)));
+    }
+
+    #[test]
+    fn test_client_id_round_trips() {
+        let mut book = fixed_clock_book();
+        let (maker, result) = book.submit(&OrderRequest {side: Side::SELL, price: Some(10), quantity: 5, client_id: Some(7) });
+        assert_eq!(result.events, vec![Event::Done {id: maker, client_id: Some(7), reason: DoneReason::Rested, remaining: 5, ts: 0}]);
+
+        let (taker, result) = book.submit(&OrderRequest {side: Side::BUY, price: Some(10), quantity: 2, client_id: Some(9) });
+        assert_eq!(result.events, vec![
+            Event::Fill {taker_id: taker, maker_id: maker, taker_client_id: Some(9), maker_client_id: Some(7), price: 10, qty: 2, ts: 0},
+            Event::Done {id: taker, client_id: Some(9), reason: DoneReason::Filled, remaining: 0, ts: 0},
+        ]);
+
+        // The resting maker keeps its tag through to cancellation
+        let result = book.cancel(maker, 0).unwrap();
+        assert_eq!(result.events, vec![Event::Done {id: maker, client_id: Some(7), reason: DoneReason::Cancelled, remaining: 3, ts: 0}]);
     }
 
     #[test]
     fn test_market_order_no_liquidity() {
         let mut book = Book::new();
         // Submit a BUY market order when there are no asks (no liquidity)
-        let req = OrderRequest {side: Side::BUY, price: None, quantity: 10};
+        let req = OrderRequest {side: Side::BUY, price: None, quantity: 10, client_id: None};
         
         let (order_id, _) = book.submit(&req);
-        assert_eq!(order_id, 1); // Should still get an ID even if no liquidity
+        assert_eq!(order_id, OrderId(1)); // Should still get an ID even if no liquidity
     }
 }
 
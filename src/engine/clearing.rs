@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashMap};
+
+use tracing::warn;
+
+use crate::engine::types::{Fill, OrderId, Side};
+
+// One owner's end-of-session result. `net_qty` is bought minus sold; `net_cash`
+// is received minus paid, in price * quantity units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settlement {
+    pub owner: u64,
+    pub net_qty: i128,
+    pub net_cash: i128,
+}
+
+// Net position and cash per owner from a trade tape. `owners` maps order ids to
+// owners; a side of a fill whose order has no owner is left out (so the totals
+// no longer net to zero), as is a fill without a price. Sorted by owner.
+pub fn compute_settlement(fills: &[Fill], owners: &HashMap<OrderId, u64>) -> Vec<Settlement> {
+    let mut net: BTreeMap<u64, (i128, i128)> = BTreeMap::new();
+    for fill in fills {
+        let Some(price) = fill.price else {
+            warn!(taker_id=%fill.taker_id, maker_id=%fill.maker_id, "Skipping fill without a price");
+            continue;
+        };
+        let qty = fill.quantity as i128;
+        let cash = price as i128 * qty;
+        let (buyer, seller) = match fill.taker_side {
+            Side::BUY => (fill.taker_id, fill.maker_id),
+            Side::SELL => (fill.maker_id, fill.taker_id),
+        };
+        if let Some(owner) = owners.get(&buyer) {
+            let entry = net.entry(*owner).or_default();
+            entry.0 += qty;
+            entry.1 -= cash;
+        }
+        if let Some(owner) = owners.get(&seller) {
+            let entry = net.entry(*owner).or_default();
+            entry.0 -= qty;
+            entry.1 += cash;
+        }
+    }
+    net.into_iter().map(|(owner, (net_qty, net_cash))| Settlement { owner, net_qty, net_cash }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::book::Book;
+    use crate::engine::types::OrderRequest;
+
+    #[test]
+    fn test_settlement_nets_to_zero_across_owners() {
+        let mut book = Book::new();
+        let mut owners = HashMap::new();
+        let mut tape = vec![];
+        // (owner, side, price, qty)
+        let orders = [(1, Side::SELL, 10, 5), (2, Side::SELL, 11, 5), (3, Side::BUY, 11, 7), (1, Side::BUY, 12, 2), (3, Side::SELL, 9, 4)];
+        for (owner, side, price, quantity) in orders {
+            let (id, result) = book.submit(&OrderRequest { side, price: Some(price), quantity, client_id: None });
+            owners.insert(id, owner);
+            tape.extend(result.events.iter().filter_map(|e| Fill::from_event(e, side)));
+        }
+
+        // 3 buys 5@10 from 1 and 2@11 from 2; 1 buys 2@11 from 2; 3 sells nothing
+        // (no bids left) and rests
+        assert_eq!(compute_settlement(&tape, &owners), vec![
+            Settlement { owner: 1, net_qty: -3, net_cash: 50 - 22 },
+            Settlement { owner: 2, net_qty: -4, net_cash: 44 },
+            Settlement { owner: 3, net_qty: 7, net_cash: -72 },
+        ]);
+        let settlements = compute_settlement(&tape, &owners);
+        assert_eq!(settlements.iter().map(|s| s.net_qty).sum::<i128>(), 0);
+        assert_eq!(settlements.iter().map(|s| s.net_cash).sum::<i128>(), 0);
+    }
+}
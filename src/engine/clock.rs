@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Source of event timestamps for a Book. Injected so simulations and tests can
+// run on a clock they control.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+// Wall-clock milliseconds since the unix epoch: the unit of every engine
+// timestamp (event ts, Resting.ts, audit ts_ms) unless a book runs on its own clock
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+// The clock a book uses when none is injected
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        now_ms()
+    }
+}
+
+impl std::fmt::Debug for dyn Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+// Deterministic clock for replays: the first reading is `start` and every
+// reading after that advances by `step`, regardless of wall time. A step of 0
+// gives a fixed clock.
+#[derive(Debug)]
+pub struct SimClock {
+    next: AtomicU64,
+    step: u64,
+}
+
+impl SimClock {
+    pub fn new(start: u64, step: u64) -> Self {
+        SimClock { next: AtomicU64::new(start), step }
+    }
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> u64 {
+        self.next.fetch_add(self.step, Ordering::Relaxed)
+    }
+}
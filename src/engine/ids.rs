@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Source of order ids for a Book. By default each book counts from its own
+// `next_order_id`; a book built with an allocator takes ids from it instead, so
+// several books (one per symbol) can share one id space.
+pub trait IdAllocator: Send + Sync {
+    // Hand out a fresh id. Never returns the same id twice.
+    fn allocate(&self) -> u64;
+
+    // The id the next `allocate` would return, for reporting only. With a shared
+    // allocator another book may take it first.
+    fn peek(&self) -> u64;
+}
+
+impl std::fmt::Debug for dyn IdAllocator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdAllocator").field("next", &self.peek()).finish()
+    }
+}
+
+// Process-wide monotonic allocator. To stay unique across restarts, start it
+// above the highest id already persisted.
+#[derive(Debug)]
+pub struct GlobalIdAllocator {
+    next: AtomicU64,
+}
+
+impl GlobalIdAllocator {
+    pub fn new(start: u64) -> Self {
+        GlobalIdAllocator { next: AtomicU64::new(start) }
+    }
+}
+
+impl IdAllocator for GlobalIdAllocator {
+    fn allocate(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn peek(&self) -> u64 {
+        self.next.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::book::Book;
+    use crate::engine::types::{OrderRequest, Side};
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_books_sharing_allocator_never_collide() {
+        let ids = Arc::new(GlobalIdAllocator::new(1_000));
+        let handles: Vec<_> = [Side::BUY, Side::SELL].into_iter().map(|side| {
+            let ids = ids.clone();
+            std::thread::spawn(move || {
+                let mut book = Book::with_id_allocator(ids);
+                (0..500).map(|i| {
+                    book.submit(&OrderRequest {side, price: Some(100 + i), quantity: 1, client_id: None }).0
+                }).collect::<Vec<_>>()
+            })
+        }).collect();
+
+        let mut seen = HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(seen.insert(id), "id {id} handed out twice");
+            }
+        }
+        assert_eq!(seen.len(), 1_000);
+        assert_eq!(ids.peek(), 2_000);
+        assert!(seen.iter().all(|id| (1_000..2_000).contains(&id.0)));
+    }
+}
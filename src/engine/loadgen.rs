@@ -0,0 +1,238 @@
+use std::time::Instant;
+
+use hdrhistogram::Histogram;
+
+use crate::engine::book::Book;
+use crate::engine::types::{OrderId, OrderRequest, Side};
+
+// One step of generated load. A cancel doesn't name an order: `run` uses `pick`
+// to choose one of the orders it has seen rest, so the stream can be generated
+// without a book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadOp {
+    Submit(OrderRequest),
+    Cancel { pick: u64 },
+}
+
+// Shape of the generated flow. The mix weights are relative (6/2/2 is 60% limit,
+// 20% market, 20% cancel). Limit prices are uniform in mid ± price_range, so
+// roughly half of them cross; quantities are uniform in min_qty..=max_qty.
+#[derive(Debug, Clone)]
+pub struct LoadConfig {
+    pub limit_weight: u32,
+    pub market_weight: u32,
+    pub cancel_weight: u32,
+    pub mid: i64,
+    pub price_range: i64,
+    pub min_qty: u64,
+    pub max_qty: u64,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        LoadConfig { limit_weight: 6, market_weight: 2, cancel_weight: 2, mid: 1_000, price_range: 10, min_qty: 1, max_qty: 100 }
+    }
+}
+
+// A LoadConfig the generator can't draw from
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum LoadConfigError {
+    #[error("All operation weights are zero")]
+    NoWeights,
+
+    #[error("min_qty {min} is above max_qty {max}")]
+    QtyRange { min: u64, max: u64 },
+
+    #[error("Negative price_range {0}")]
+    NegativePriceRange(i64),
+
+    #[error("mid {mid} ± price_range {range} doesn't fit an i64 price")]
+    PriceOutOfRange { mid: i64, range: i64 },
+}
+
+impl LoadConfig {
+    pub fn validate(&self) -> Result<(), LoadConfigError> {
+        if self.limit_weight == 0 && self.market_weight == 0 && self.cancel_weight == 0 {
+            return Err(LoadConfigError::NoWeights);
+        }
+        if self.min_qty > self.max_qty {
+            return Err(LoadConfigError::QtyRange { min: self.min_qty, max: self.max_qty });
+        }
+        if self.price_range < 0 {
+            return Err(LoadConfigError::NegativePriceRange(self.price_range));
+        }
+        if self.mid.checked_sub(self.price_range).is_none() || self.mid.checked_add(self.price_range).is_none() {
+            return Err(LoadConfigError::PriceOutOfRange { mid: self.mid, range: self.price_range });
+        }
+        Ok(())
+    }
+}
+
+// Per-operation submit/cancel latency over a run, in nanoseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyReport {
+    pub ops: u64,
+    pub p50_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+}
+
+// Seeded random order flow for benchmarks and soak tests. The same seed and
+// config always give the same stream. The config is checked once in `new`.
+#[derive(Debug, Clone)]
+pub struct OrderGenerator {
+    config: LoadConfig,
+    state: u64,
+}
+
+impl OrderGenerator {
+    pub fn new(config: LoadConfig, seed: u64) -> Result<Self, LoadConfigError> {
+        config.validate()?;
+        Ok(OrderGenerator { config, state: seed })
+    }
+
+    // splitmix64: small, fast and good enough for load shaping
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..n (n > 0)
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    // Uniform in lo..=hi (lo <= hi), including the full u64 range
+    fn between(&mut self, lo: u64, hi: u64) -> u64 {
+        match (hi - lo).checked_add(1) {
+            Some(n) => lo + self.below(n),
+            None => self.next_u64(),
+        }
+    }
+
+    pub fn next_op(&mut self) -> LoadOp {
+        let c = &self.config;
+        let (limit, market, cancel) = (c.limit_weight as u64, c.market_weight as u64, c.cancel_weight as u64);
+        let roll = self.below(limit + market + cancel);
+        if roll >= limit + market {
+            return LoadOp::Cancel { pick: self.next_u64() };
+        }
+
+        let side = if self.below(2) == 0 { Side::BUY } else { Side::SELL };
+        let quantity = self.between(self.config.min_qty, self.config.max_qty);
+        let price = if roll < limit {
+            // The offset can exceed i64::MAX, but the sum is in range once validated
+            let range = self.config.price_range;
+            Some((self.config.mid - range).wrapping_add(self.between(0, 2 * range as u64) as i64))
+        } else {
+            None
+        };
+        LoadOp::Submit(OrderRequest { side, price, quantity, client_id: None })
+    }
+
+    // Drive `n` generated operations through `book`, timing each one. A cancel
+    // when nothing has rested yet is still counted (it's a miss on the book).
+    pub fn run(&mut self, book: &mut Book, n: usize) -> LatencyReport {
+        let mut histogram = Histogram::<u64>::new(3).expect("3 significant figures is a valid precision");
+        let mut resting: Vec<OrderId> = vec![];
+        for _ in 0..n {
+            let op = self.next_op();
+            let start = Instant::now();
+            match op {
+                LoadOp::Submit(req) => {
+                    let (id, result) = book.submit(&req);
+                    if result.resting_qty > 0 {
+                        resting.push(id);
+                    }
+                }
+                LoadOp::Cancel { pick } => {
+                    if !resting.is_empty() {
+                        let id = resting.swap_remove((pick % resting.len() as u64) as usize);
+                        let ts = book.now();
+                        book.cancel(id, ts);
+                    }
+                }
+            }
+            histogram.saturating_record(start.elapsed().as_nanos() as u64);
+        }
+
+        LatencyReport {
+            ops: histogram.len(),
+            p50_ns: histogram.value_at_quantile(0.5),
+            p99_ns: histogram.value_at_quantile(0.99),
+            p999_ns: histogram.value_at_quantile(0.999),
+            max_ns: histogram.max(),
+        }
+    }
+}
+
+impl Iterator for OrderGenerator {
+    type Item = LoadOp;
+
+    fn next(&mut self) -> Option<LoadOp> {
+        Some(self.next_op())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator_honours_count_and_mix() {
+        let config = LoadConfig { limit_weight: 5, market_weight: 3, cancel_weight: 2, mid: 500, price_range: 4, min_qty: 2, max_qty: 8 };
+        let ops: Vec<LoadOp> = OrderGenerator::new(config, 42).unwrap().take(10_000).collect();
+        assert_eq!(ops.len(), 10_000);
+
+        let (mut limits, mut markets, mut cancels) = (0, 0, 0);
+        for op in &ops {
+            match op {
+                LoadOp::Submit(OrderRequest { price: Some(price), quantity, .. }) => {
+                    assert!((496..=504).contains(price) && (2..=8).contains(quantity));
+                    limits += 1;
+                }
+                LoadOp::Submit(_) => markets += 1,
+                LoadOp::Cancel { .. } => cancels += 1,
+            }
+        }
+        // Within 2 percentage points of the configured 50/30/20
+        for (count, expected) in [(limits, 5_000), (markets, 3_000), (cancels, 2_000)] {
+            assert!((count as i64 - expected).abs() <= 200, "got {count}, expected about {expected}");
+        }
+
+        assert_eq!(OrderGenerator::new(LoadConfig::default(), 7).unwrap().take(100).collect::<Vec<_>>(),
+                   OrderGenerator::new(LoadConfig::default(), 7).unwrap().take(100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_run_reports_every_operation() {
+        let mut book = Book::new();
+        let report = OrderGenerator::new(LoadConfig::default(), 1).unwrap().run(&mut book, 2_000);
+        assert_eq!(report.ops, 2_000);
+        assert!(report.p50_ns <= report.p99_ns && report.p99_ns <= report.p999_ns && report.p999_ns <= report.max_ns);
+        assert!(book.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_configs_are_rejected() {
+        let base = LoadConfig::default();
+        let reject = |config: LoadConfig| OrderGenerator::new(config, 0).unwrap_err();
+        assert_eq!(reject(LoadConfig { limit_weight: 0, market_weight: 0, cancel_weight: 0, ..base.clone() }), LoadConfigError::NoWeights);
+        assert_eq!(reject(LoadConfig { min_qty: 9, max_qty: 8, ..base.clone() }), LoadConfigError::QtyRange { min: 9, max: 8 });
+        assert_eq!(reject(LoadConfig { price_range: -1, ..base.clone() }), LoadConfigError::NegativePriceRange(-1));
+        assert_eq!(reject(LoadConfig { mid: i64::MAX, price_range: 1, ..base.clone() }), LoadConfigError::PriceOutOfRange { mid: i64::MAX, range: 1 });
+
+        // The widest valid ranges draw without overflowing
+        let wide = LoadConfig { limit_weight: 1, market_weight: 0, cancel_weight: 0, mid: 0, price_range: i64::MAX, min_qty: 0, max_qty: u64::MAX };
+        assert_eq!(OrderGenerator::new(wide, 3).unwrap().take(1_000).count(), 1_000);
+        let point = LoadConfig { price_range: 0, min_qty: 5, max_qty: 5, ..base };
+        assert!(OrderGenerator::new(point, 3).unwrap().take(100).all(|op| match op {
+            LoadOp::Submit(req) => req.quantity == 5 && req.price.is_none_or(|p| p == 1_000),
+            LoadOp::Cancel { .. } => true,
+        }));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Unbounded};
+
+use crate::engine::types::{DoneReason, Event, Level, Order, OrderId, Side};
+
+// The matching algorithm on its own: no id index, logging or book config. Matches
+// `order` against the opposite side, best price first and FIFO within a level,
+// walking no further than `order.price` (a market order has no bound). Fully
+// filled makers are removed from their level and emptied levels are dropped.
+//
+// Returns the Fills plus a Done { Filled } for every maker taken to zero, in the
+// order documented on `Event`, and the taker's unfilled quantity. The taker's own
+// Done is left to the caller, which knows whether the residual rests or is
+// cancelled.
+pub fn match_order(bids: &mut BTreeMap<i64, Level>, asks: &mut BTreeMap<i64, Level>, order: &Order, ts: u64) -> (Vec<Event>, u64) {
+    let side = order.side;
+    let levels = match side {
+        Side::BUY => asks,
+        Side::SELL => bids,
+    };
+    let mut events = vec![];
+    let mut remaining_qty = order.quantity;
+    let mut last_price: Option<i64> = None;
+    while remaining_qty > 0 {
+        let next_price = match (side, last_price) {
+            (Side::BUY, None) => levels.keys().next().copied(),
+            (Side::BUY, Some(p)) => levels.range((Excluded(p), Unbounded)).next().map(|(p, _)| *p),
+            (Side::SELL, None) => levels.keys().next_back().copied(),
+            (Side::SELL, Some(p)) => levels.range(..p).next_back().map(|(p, _)| *p),
+        };
+        let price = match next_price {
+            Some(p) => p,
+            None => break,
+        };
+        let crosses = match (side, order.price) {
+            (_, None) => true,
+            (Side::BUY, Some(limit)) => price <= limit,
+            (Side::SELL, Some(limit)) => price >= limit,
+        };
+        if !crosses { break; }
+        last_price = Some(price);
+
+        let Some(level) = levels.get_mut(&price) else { break };
+        remaining_qty = fill_against_level(order, remaining_qty, price, level, ts, &mut events);
+        if level.is_empty() {
+            levels.remove(&price);
+        }
+    }
+    (events, remaining_qty)
+}
+
+fn fill_against_level(taker: &Order, mut remaining_qty: u64, price: i64, level: &mut Level, ts: u64, events: &mut Vec<Event>) -> u64 {
+    for resting_order in level.orders.iter_mut() {
+        if resting_order.active && resting_order.remaining > 0 && remaining_qty > 0 {
+            let fill_qty = std::cmp::min(remaining_qty, resting_order.remaining);
+            resting_order.remaining -= fill_qty;
+            level.total_remaining -= fill_qty as u128;
+            remaining_qty -= fill_qty;
+
+            events.push(Event::Fill {
+                taker_id: taker.id,
+                maker_id: resting_order.id,
+                taker_client_id: taker.client_id,
+                maker_client_id: resting_order.client_id,
+                price,
+                qty: fill_qty,
+                ts
+            });
+
+            // Maker is done exactly once, when its fill takes it to zero
+            if resting_order.remaining == 0 {
+                events.push(Event::Done {id: resting_order.id, client_id: resting_order.client_id, reason: DoneReason::Filled, remaining: 0, ts});
+            }
+
+            if remaining_qty == 0 { break; }
+        }
+    }
+    level.orders.retain(|r| !(r.active && r.remaining == 0));
+    remaining_qty
+}
+
+// Presentation-only view of a taker's events with one Fill per price level
+// instead of one per maker: the merged Fill carries the summed qty and names
+// the first (oldest) maker hit at that price, and the Done of each maker it
+// emptied follows it. Everything else passes through in order. Nothing that
+// needs per-maker detail (observers, audit, settlement) should see the output.
+pub fn coalesce_fills(events: Vec<Event>) -> Vec<Event> {
+    let mut out: Vec<Event> = Vec::with_capacity(events.len());
+    // Index of the Fill being merged into, and the maker Dones held back to follow it
+    let mut merged: Option<usize> = None;
+    let mut maker_dones = vec![];
+    for event in events {
+        let merge_key = merged.and_then(|i| match out[i] {
+            Event::Fill { taker_id, price, .. } => Some((taker_id, price)),
+            _ => None,
+        });
+        match (&event, merge_key) {
+            (Event::Fill { taker_id, price, qty, .. }, Some(key)) if (*taker_id, *price) == key => {
+                if let Some(Event::Fill { qty: merged_qty, .. }) = merged.map(|i| &mut out[i]) {
+                    *merged_qty += qty;
+                }
+            }
+            (Event::Done { id, reason: DoneReason::Filled, .. }, Some((taker_id, _))) if *id != taker_id => {
+                maker_dones.push(event);
+            }
+            _ => {
+                out.append(&mut maker_dones);
+                merged = matches!(event, Event::Fill { .. }).then_some(out.len());
+                out.push(event);
+            }
+        }
+    }
+    out.append(&mut maker_dones);
+    out
+}
+
+// Makers a `match_order` result emptied, i.e. every Done { Filled } other than the taker's
+pub fn exhausted_makers(events: &[Event], taker_id: OrderId) -> impl Iterator<Item = OrderId> + '_ {
+    events.iter().filter_map(move |event| match event {
+        Event::Done { id, reason: DoneReason::Filled, .. } if *id != taker_id => Some(*id),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::types::Resting;
+
+    fn side_with(orders: &[(u64, i64, u64)]) -> BTreeMap<i64, Level> {
+        let mut levels: BTreeMap<i64, Level> = BTreeMap::new();
+        for &(id, price, qty) in orders {
+            levels.entry(price).or_default().push_back(Resting {
+                id: OrderId(id), price: Some(price), quantity: qty, remaining: qty, ts: 0, active: true, client_id: None,
+            });
+        }
+        levels
+    }
+
+    fn taker(side: Side, price: Option<i64>, quantity: u64) -> Order {
+        Order { id: OrderId(99), price, quantity, side, client_id: None }
+    }
+
+    fn fill(maker: u64, price: i64, qty: u64) -> Event {
+        Event::Fill { taker_id: OrderId(99), maker_id: OrderId(maker), taker_client_id: None, maker_client_id: None, price, qty, ts: 0 }
+    }
+
+    fn maker_done(maker: u64) -> Event {
+        Event::Done { id: OrderId(maker), client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0 }
+    }
+
+    #[test]
+    fn test_partial_fill_leaves_maker_resting() {
+        let mut bids = BTreeMap::new();
+        let mut asks = side_with(&[(1, 10, 5), (2, 10, 5)]);
+        let (events, residual) = match_order(&mut bids, &mut asks, &taker(Side::BUY, Some(10), 7), 0);
+
+        assert_eq!(events, vec![fill(1, 10, 5), maker_done(1), fill(2, 10, 2)]);
+        assert_eq!(residual, 0);
+        assert_eq!(exhausted_makers(&events, OrderId(99)).collect::<Vec<_>>(), vec![OrderId(1)]);
+        let level = &asks[&10];
+        assert_eq!((level.len(), level.total_remaining()), (1, 3));
+        assert!(bids.is_empty());
+    }
+
+    #[test]
+    fn test_exhausted_levels_are_dropped_up_to_limit() {
+        let mut bids = side_with(&[(1, 12, 4), (2, 11, 3), (3, 10, 6)]);
+        let mut asks = BTreeMap::new();
+        let (events, residual) = match_order(&mut bids, &mut asks, &taker(Side::SELL, Some(11), 10), 0);
+
+        assert_eq!(events, vec![fill(1, 12, 4), maker_done(1), fill(2, 11, 3), maker_done(2)]);
+        assert_eq!(residual, 3);
+        assert_eq!(bids.keys().copied().collect::<Vec<_>>(), vec![10]);
+
+        // A market order walks every level and reports what it couldn't fill
+        let (events, residual) = match_order(&mut bids, &mut asks, &taker(Side::SELL, None, 10), 0);
+        assert_eq!(events, vec![fill(3, 10, 6), maker_done(3)]);
+        assert_eq!(residual, 4);
+        assert!(bids.is_empty());
+    }
+
+    #[test]
+    fn test_coalesce_merges_each_level_and_holds_back_maker_dones() {
+        let mut bids = BTreeMap::new();
+        let mut asks = side_with(&[(1, 10, 3), (2, 10, 4), (3, 10, 5), (4, 11, 5)]);
+        let (events, residual) = match_order(&mut bids, &mut asks, &taker(Side::BUY, Some(11), 14), 0);
+        assert_eq!(residual, 0);
+        assert_eq!(events, vec![
+            fill(1, 10, 3), maker_done(1), fill(2, 10, 4), maker_done(2), fill(3, 10, 5), maker_done(3), fill(4, 11, 2),
+        ]);
+
+        assert_eq!(coalesce_fills(events), vec![fill(1, 10, 12), maker_done(1), maker_done(2), maker_done(3), fill(4, 11, 2)]);
+    }
+}
@@ -1,2 +1,11 @@
 pub mod types;
-pub mod book;
\ No newline at end of file
+pub mod book;
+pub mod matcher;
+pub mod observer;
+#[cfg(feature = "async")]
+pub mod async_book;
+pub mod audit;
+pub mod ids;
+pub mod clock;
+pub mod loadgen;
+pub mod clearing;
@@ -0,0 +1,15 @@
+use crate::engine::types::Event;
+
+// Synchronous hook into the book's event stream. The book calls `on_event` for
+// every event an operation produces, in order, before that operation returns, so
+// in-process consumers (e.g. a risk check) see fills without going through a channel.
+// Keep it cheap: it runs on the matching thread.
+pub trait EventObserver: Send {
+    fn on_event(&mut self, event: &Event);
+}
+
+impl std::fmt::Debug for dyn EventObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventObserver")
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Side {
     BUY,
@@ -8,41 +10,248 @@ pub enum Side {
 pub struct OrderRequest {
     pub price: Option<i64>,
     pub quantity: u64,
-    pub side: Side
+    pub side: Side,
+    // Opaque caller tag echoed on this order's events; never interpreted by the engine
+    pub client_id: Option<u64>
+}
+
+impl OrderRequest {
+    // Parse a compact text command into a request. Case-insensitive; accepts
+    //   buy <price> <qty>          sell <price> <qty>
+    //   market_buy <qty>           market_sell <qty>
+    //   limit BUY|SELL <price> <qty>
+    //   market BUY|SELL <qty>
+    pub fn parse(cmd: &str) -> Result<OrderRequest, ParseError> {
+        let tokens: Vec<&str> = cmd.split_whitespace().collect();
+        let Some(first) = tokens.first() else {
+            return Err(ParseError::Empty);
+        };
+        let command = first.to_ascii_lowercase();
+        let args = &tokens[1..];
+
+        let expect_args = |expected: usize| {
+            if args.len() == expected {
+                Ok(())
+            } else {
+                Err(ParseError::WrongArgCount { command: command.clone(), expected, got: args.len() })
+            }
+        };
+
+        match command.as_str() {
+            "buy" | "sell" => {
+                expect_args(2)?;
+                let side = if command == "buy" { Side::BUY } else { Side::SELL };
+                Ok(OrderRequest { side, price: Some(parse_price(args[0])?), quantity: parse_qty(args[1])?, client_id: None })
+            }
+            "market_buy" | "market_sell" => {
+                expect_args(1)?;
+                let side = if command == "market_buy" { Side::BUY } else { Side::SELL };
+                Ok(OrderRequest { side, price: None, quantity: parse_qty(args[0])?, client_id: None })
+            }
+            "limit" => {
+                expect_args(3)?;
+                Ok(OrderRequest { side: parse_side(args[0])?, price: Some(parse_price(args[1])?), quantity: parse_qty(args[2])?, client_id: None })
+            }
+            "market" => {
+                expect_args(2)?;
+                Ok(OrderRequest { side: parse_side(args[0])?, price: None, quantity: parse_qty(args[1])?, client_id: None })
+            }
+            _ => Err(ParseError::UnknownCommand(first.to_string())),
+        }
+    }
+}
+
+fn parse_side(s: &str) -> Result<Side, ParseError> {
+    match s.to_ascii_uppercase().as_str() {
+        "BUY" => Ok(Side::BUY),
+        "SELL" => Ok(Side::SELL),
+        _ => Err(ParseError::InvalidSide(s.to_string())),
+    }
+}
+
+fn parse_price(s: &str) -> Result<i64, ParseError> {
+    s.parse().map_err(|_| ParseError::InvalidNumber { field: "price", value: s.to_string() })
+}
+
+fn parse_qty(s: &str) -> Result<u64, ParseError> {
+    s.parse().map_err(|_| ParseError::InvalidNumber { field: "quantity", value: s.to_string() })
+}
+
+// Engine-assigned order id. A distinct type so ids can't be mixed up with
+// prices, quantities or timestamps; serializes as the bare integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct OrderId(pub u64);
+
+impl OrderId {
+    // Counterparty for trades that happened on another venue (see
+    // `Book::apply_external_fill`). Never allocated to an order, so it has no
+    // Received/Done of its own.
+    pub const EXTERNAL: OrderId = OrderId(u64::MAX);
+}
+
+impl From<u64> for OrderId {
+    fn from(id: u64) -> Self {
+        OrderId(id)
+    }
+}
+
+impl From<OrderId> for u64 {
+    fn from(id: OrderId) -> Self {
+        id.0
+    }
+}
+
+impl std::fmt::Display for OrderId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 // Order with assigned ID (for internal use)
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Order {
-    pub id: u64,
+    pub id: OrderId,
     pub price: Option<i64>,
     pub quantity: u64,
-    pub side: Side
+    pub side: Side,
+    pub client_id: Option<u64>
 }
 
 // Resting order in the book (mutable remaining)
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Resting {
-    pub id: u64,
+    pub id: OrderId,
     pub price: Option<i64>,
     pub quantity: u64,
+    // When it rested, in epoch millis (the book's clock)
     pub ts: u64,
     pub remaining: u64,
-    pub active: bool
+    pub active: bool,
+    pub client_id: Option<u64>
+}
+
+// One price level: resting orders in time priority plus the live size they add
+// up to. `total_remaining` counts active orders only and is kept in step by every
+// insert, fill, amend and cancel, so top-of-book and depth never re-sum the queue.
+// It is held as u128 so it stays exact even when the sum no longer fits a u64;
+// the book's AggregateOverflow policy decides what readers see in that case.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Level {
+    pub(crate) orders: VecDeque<Resting>,
+    pub(crate) total_remaining: u128,
+}
+
+impl Level {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_back(&mut self, resting: Resting) {
+        if resting.active {
+            self.total_remaining += resting.remaining as u128;
+        }
+        self.orders.push_back(resting);
+    }
+
+    // Remove an order by id, wherever it sits in the queue
+    pub fn remove(&mut self, id: OrderId) -> Option<Resting> {
+        let pos = self.orders.iter().position(|r| r.id == id)?;
+        let removed = self.orders.remove(pos)?;
+        if removed.active {
+            self.total_remaining -= removed.remaining as u128;
+        }
+        Some(removed)
+    }
+
+    // Shrink an order's open size to `remaining` without moving it in the queue.
+    // None if the id isn't here or `remaining` would grow the order.
+    pub fn reduce_to(&mut self, id: OrderId, remaining: u64) -> Option<&Resting> {
+        let resting = self.orders.iter_mut().find(|r| r.id == id)?;
+        if remaining > resting.remaining {
+            return None;
+        }
+        let reduce_by = resting.remaining - remaining;
+        resting.remaining = remaining;
+        resting.quantity -= reduce_by;
+        if resting.active {
+            self.total_remaining -= reduce_by as u128;
+        }
+        Some(resting)
+    }
+
+    // Live size at this level, saturated to u64::MAX
+    pub fn total_remaining(&self) -> u64 {
+        u64::try_from(self.total_remaining).unwrap_or(u64::MAX)
+    }
+
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, Resting> {
+        self.orders.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
 }
 
  // Fill (execution) event
  #[derive(Debug, PartialEq, Eq)]
 pub struct Fill {
-    pub taker_id: u64,
-    pub maker_id: u64,
+    pub taker_id: OrderId,
+    pub maker_id: OrderId,
+    // Side of the taker; the maker was on the other side
+    pub taker_side: Side,
     pub price: Option<i64>,
     pub quantity: u64,
+    // Epoch millis, as on the Event::Fill
     pub ts: u64
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum DoneReason { Filled, Rested, Cancelled, Rejected }
+impl Fill {
+    // Trade-tape entry for an Event::Fill. The event doesn't carry the taker's
+    // side, so the caller supplies it from the order it submitted.
+    pub fn from_event(event: &Event, taker_side: Side) -> Option<Fill> {
+        match *event {
+            Event::Fill { taker_id, maker_id, price, qty, ts, .. } =>
+                Some(Fill { taker_id, maker_id, taker_side, price: Some(price), quantity: qty, ts }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DoneReason {
+    Filled,
+    Rested,
+    Cancelled,
+    Rejected(RejectReason),
+    // Immediate-or-cancel style order (e.g. market) that filled in part and had the residual dropped
+    PartiallyFilledThenCancelled,
+}
+
+// Why an order was rejected, so a client can tell a request it should fix
+// (InvalidQuantity, InvalidPrice, ...) from a book state it might wait out
+// (Halted, NoLiquidity)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RejectReason {
+    InvalidQuantity,
+    SizeOutOfBounds,
+    // Limit price missing or not positive
+    InvalidPrice,
+    NotionalTooLarge,
+    AggregateOverflow,
+    Halted,
+    // Market order with nothing on the other side
+    NoLiquidity,
+    // cancel_replace of an order that isn't resting
+    OrderNotFound,
+    // Valid on its own, but another order in the same atomic batch was rejected
+    BatchRolledBack,
+}
 
 // Error types for better error handling
 #[derive(thiserror::Error, Debug)]
@@ -51,7 +260,7 @@ pub enum BookError {
     InvalidQuantity { quantity: u64 },
     
     #[error("Order {id} not found")]
-    OrderNotFound { id: u64 },
+    OrderNotFound { id: OrderId },
     
     #[error("No liquidity available for {side:?} market order")]
     NoLiquidity { side: Side },
@@ -61,6 +270,21 @@ pub enum BookError {
     
     #[error("Invalid price for limit order")]
     InvalidPrice,
+
+    #[error("Book is halted")]
+    Halted,
+
+    #[error("Order quantity {qty} outside allowed range {min}..={max}")]
+    SizeOutOfBounds { qty: u64, min: u64, max: u64 },
+
+    #[error("Size limits {min}..={max} are empty")]
+    InvalidSizeLimits { min: u64, max: u64 },
+
+    #[error("Aggregate size at {side:?} level {price} would overflow")]
+    AggregateOverflow { price: i64, side: Side },
+
+    #[error("Order notional {notional} exceeds ceiling {max}")]
+    NotionalTooLarge { notional: u128, max: u128 },
     
     #[error("Integer conversion error: {source}")]
     ConversionError { 
@@ -69,17 +293,178 @@ pub enum BookError {
     },
 }
 
+// Errors from parsing a text order command
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Empty command")]
+    Empty,
+
+    #[error("Unknown command '{0}'")]
+    UnknownCommand(String),
+
+    #[error("'{command}' takes {expected} argument(s), got {got}")]
+    WrongArgCount { command: String, expected: usize, got: usize },
+
+    #[error("Invalid side '{0}', expected BUY or SELL")]
+    InvalidSide(String),
+
+    #[error("Invalid {field} '{value}'")]
+    InvalidNumber { field: &'static str, value: String },
+}
+
+// Ordering contract for the events of one operation:
+//  - a taker's Fills come best price first (ascending asks for a buy, descending
+//    bids for a sell) and, within a price level, oldest maker first
+//  - a maker's Done { Filled } immediately follows the Fill that emptied it (in a
+//    coalesced SubmitResult, the level's merged Fill followed by all the makers it
+//    emptied)
+//  - the taker's own Done follows all of its Fills; orders evicted by the level cap
+//    when it rests are reported after it
+// Every `ts` is epoch milliseconds from the book's clock (a sim book's clock may
+// count in its own units).
 #[derive(Debug, PartialEq, Eq)]
 pub enum Event {
-    Ack  { id: u64, ts: u64 },
-    Fill { taker_id: u64, maker_id: u64, price: i64, qty: u64, ts: u64 },
-    Done { id: u64, reason: DoneReason, ts: u64 },
+    Ack  { id: OrderId, client_id: Option<u64>, ts: u64 },
+    Fill { taker_id: OrderId, maker_id: OrderId, taker_client_id: Option<u64>, maker_client_id: Option<u64>, price: i64, qty: u64, ts: u64 },
+    // `remaining` is the order's unfilled quantity when it reached this state:
+    // what rests on the book for Rested, what was pulled for Cancelled, what went
+    // unexecuted for Rejected, and 0 for Filled
+    Done { id: OrderId, client_id: Option<u64>, reason: DoneReason, remaining: u64, ts: u64 },
 }
 
+impl Event {
+    pub fn ts(&self) -> u64 {
+        match self {
+            Event::Ack { ts, .. } | Event::Fill { ts, .. } | Event::Done { ts, .. } => *ts,
+        }
+    }
+}
+
+// Outcome of one book operation. `events` is the full detail; the other fields
+// summarise it for the order the operation was about (the submitted order, or
+// the one cancelled/replaced), so consumers don't each re-fold the events.
 #[derive(Debug, PartialEq, Eq)]
 pub struct SubmitResult {
-    pub events: Vec<Event>
+    pub events: Vec<Event>,
+    // Fills this order took part in, and their total size
+    pub fill_count: usize,
+    pub filled_qty: u64,
+    // Size left on the book, non-zero only when the order ended up Rested
+    pub resting_qty: u64,
+    // Reason on this order's Done event, if one was emitted
+    pub terminal_reason: Option<DoneReason>,
+}
+
+impl SubmitResult {
+    pub fn new(id: OrderId, events: Vec<Event>) -> Self {
+        let mut result = SubmitResult { events: vec![], fill_count: 0, filled_qty: 0, resting_qty: 0, terminal_reason: None };
+        for event in &events {
+            match *event {
+                Event::Fill { taker_id, maker_id, qty, .. } if taker_id == id || maker_id == id => {
+                    result.fill_count += 1;
+                    result.filled_qty += qty;
+                }
+                Event::Done { id: done_id, reason, remaining, .. } if done_id == id => {
+                    result.terminal_reason = Some(reason);
+                    result.resting_qty = if reason == DoneReason::Rested { remaining } else { 0 };
+                }
+                _ => {}
+            }
+        }
+        result.events = events;
+        result
+    }
+}
+
+// What happens when the live size at one price level no longer fits a u64.
+// Saturate accepts the order and reports u64::MAX; Error rejects the order that
+// would overflow; Panic treats it as a bug, which is what tests usually want.
+// The policy also covers reads: level sizes (best_bid/best_ask, depth, cost
+// queries) and the totals built from them (cumulative_depth, queue_ahead,
+// resting_notional) clamp to the type's max under Saturate and Error, and
+// panic under Panic, so switching to Panic on a book that already overflowed
+// makes those accessors panic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateOverflow {
+    #[default]
+    Saturate,
+    Error,
+    Panic,
+}
+
+// Which submissions a halted book rejects. Cancels are always accepted so
+// participants can pull their quotes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum HaltPolicy {
+    // Market orders and limits that would cross; passive limits can still rest
+    #[default]
+    AggressiveOnly,
+    AllOrders
+}
+
+// A resting order as the book currently holds it: what was submitted plus what is
+// left. `ts` is when it was accepted onto the book, in epoch millis.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ResolvedOrder {
+    pub id: OrderId,
+    pub client_id: Option<u64>,
+    pub side: Side,
+    pub price: i64,
+    pub quantity: u64,
+    pub remaining: u64,
+    pub ts: u64,
+}
+
+// Point-in-time metadata about a book, for tooling and display
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BookStats {
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    pub total_orders: usize,
+    pub next_order_id: u64
 }
 
 pub type BookResult<T> = Result<T, BookError>;
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compact_forms() {
+        assert_eq!(OrderRequest::parse("buy 100 5"), Ok(OrderRequest { side: Side::BUY, price: Some(100), quantity: 5, client_id: None }));
+        assert_eq!(OrderRequest::parse("SELL -3 7"), Ok(OrderRequest { side: Side::SELL, price: Some(-3), quantity: 7, client_id: None }));
+        assert_eq!(OrderRequest::parse("market_buy 10"), Ok(OrderRequest { side: Side::BUY, price: None, quantity: 10, client_id: None }));
+        assert_eq!(OrderRequest::parse("  market_sell   2 "), Ok(OrderRequest { side: Side::SELL, price: None, quantity: 2, client_id: None }));
+    }
+
+    #[test]
+    fn test_parse_cli_forms() {
+        assert_eq!(OrderRequest::parse("limit sell 101 3"), Ok(OrderRequest { side: Side::SELL, price: Some(101), quantity: 3, client_id: None }));
+        assert_eq!(OrderRequest::parse("market BUY 4"), Ok(OrderRequest { side: Side::BUY, price: None, quantity: 4, client_id: None }));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(OrderRequest::parse("   "), Err(ParseError::Empty));
+        assert_eq!(OrderRequest::parse("hold 1 2"), Err(ParseError::UnknownCommand("hold".into())));
+        assert_eq!(OrderRequest::parse("buy 100"), Err(ParseError::WrongArgCount { command: "buy".into(), expected: 2, got: 1 }));
+        assert_eq!(OrderRequest::parse("market_sell 1 2"), Err(ParseError::WrongArgCount { command: "market_sell".into(), expected: 1, got: 2 }));
+        assert_eq!(OrderRequest::parse("limit long 1 2"), Err(ParseError::InvalidSide("long".into())));
+        assert_eq!(OrderRequest::parse("buy abc 2"), Err(ParseError::InvalidNumber { field: "price", value: "abc".into() }));
+        assert_eq!(OrderRequest::parse("market_buy -1"), Err(ParseError::InvalidNumber { field: "quantity", value: "-1".into() }));
+
+        let err = OrderRequest::parse("sell 1.5 2").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid price '1.5'");
+    }
+
+    #[test]
+    fn test_order_id_serializes_as_bare_integer() {
+        assert_eq!(serde_json::to_string(&OrderId(42)).unwrap(), "42");
+        assert_eq!(serde_json::from_str::<OrderId>("42").unwrap(), OrderId(42));
+        assert_eq!(serde_json::to_string(&Some(OrderId(7))).unwrap(), "7");
+        assert_eq!(OrderId(42).to_string(), "42");
+        assert_eq!(u64::from(OrderId::from(9)), 9);
+    }
+}
@@ -1,26 +1,102 @@
-mod engine;
-use engine::book::Book;
-use engine::types::{Order, OrderRequest, Side};
+use lobx_rs::engine::book::Book;
+use lobx_rs::engine::types::{Event, Order, OrderId, OrderRequest, ParseError, ResolvedOrder};
 use std::collections::HashMap;
 use std::io::{self, Write};
 use tracing_subscriber::EnvFilter;
 use anyhow::Result;
 
-fn print_top(book: &Book) {
-    let bb = book.best_bid().map(|(p,q)| format!("BID=({p}, {q})")).unwrap_or("BID=None".into());
-    let ba = book.best_ask().map(|(p,q)| format!("ASK=({p}, {q})")).unwrap_or("ASK=None".into());
-    let spread = book.spread().map(|s| format!("SPREAD={s}")).unwrap_or("SPREAD=None".into());
+// Decimal scaling for display only: the book works in integer units, and a
+// scale of 10^n means the last n digits are decimals. Defaults to 1 (raw integers).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DisplayScale {
+    price: u64,
+    size: u64,
+}
+
+impl DisplayScale {
+    fn from_env() -> Self {
+        DisplayScale {
+            price: scale_from_env("LOBX_PRICE_SCALE"),
+            size: scale_from_env("LOBX_SIZE_SCALE"),
+        }
+    }
+
+    fn price(&self, value: i64) -> String {
+        format_scaled(value as i128, self.price)
+    }
+
+    fn size(&self, value: u64) -> String {
+        format_scaled(value as i128, self.size)
+    }
+}
+
+fn scale_from_env(var: &str) -> u64 {
+    match std::env::var(var) {
+        Ok(raw) => match raw.trim().parse::<u64>() {
+            Ok(scale) if is_power_of_ten(scale) => scale,
+            _ => {
+                eprintln!("{var}={raw} is not a power of ten, displaying raw integers");
+                1
+            }
+        },
+        Err(_) => 1,
+    }
+}
+
+fn is_power_of_ten(scale: u64) -> bool {
+    scale.checked_ilog10().is_some_and(|n| 10u64.pow(n) == scale)
+}
+
+fn format_scaled(value: i128, scale: u64) -> String {
+    let decimals = scale.ilog10() as usize;
+    if decimals == 0 {
+        return value.to_string();
+    }
+    let scale = scale as i128;
+    let sign = if value < 0 { "-" } else { "" };
+    let abs = value.abs();
+    format!("{sign}{}.{:0width$}", abs / scale, abs % scale, width = decimals)
+}
+
+fn print_top(book: &Book, scale: &DisplayScale) {
+    let bb = book.best_bid().map(|(p,q)| format!("BID=({}, {})", scale.price(p), scale.size(q))).unwrap_or("BID=None".into());
+    let ba = book.best_ask().map(|(p,q)| format!("ASK=({}, {})", scale.price(p), scale.size(q))).unwrap_or("ASK=None".into());
+    let spread = book.spread().map(|s| format!("SPREAD={}", scale.price(s))).unwrap_or("SPREAD=None".into());
     println!("TOP: {bb}  {ba}  {spread}");
 }
 
-fn parse_side(s: &str) -> Option<Side> {
-    match s.to_ascii_uppercase().as_str() {
-        "BUY" => Some(Side::BUY),
-        "SELL" => Some(Side::SELL),
-        _ => None
+fn describe_order(req: &OrderRequest, scale: &DisplayScale) -> String {
+    let price = req.price.map(|p| scale.price(p)).unwrap_or_else(|| "MKT".into());
+    format!("{:?} {} @ {}", req.side, scale.size(req.quantity), price)
+}
+
+// `cancel <order_id>` / `show <order_id>`, with the same errors as OrderRequest::parse
+fn parse_order_id(tokens: &[&str]) -> Result<OrderId, ParseError> {
+    match tokens {
+        [_, id] => id.parse::<u64>().map(OrderId).map_err(|_| ParseError::InvalidNumber { field: "order_id", value: id.to_string() }),
+        _ => Err(ParseError::WrongArgCount { command: tokens.first().unwrap_or(&"").to_ascii_lowercase(), expected: 1, got: tokens.len().saturating_sub(1) }),
     }
 }
 
+fn describe_event(event: &Event, scale: &DisplayScale) -> String {
+    match event {
+        Event::Ack { id, .. } => format!("Ack {id}"),
+        Event::Fill { taker_id, maker_id, price, qty, .. } =>
+            format!("Fill {} @ {} (taker {taker_id}, maker {maker_id})", scale.size(*qty), scale.price(*price)),
+        Event::Done { id, reason, remaining, .. } =>
+            format!("Done {id} {reason:?}, {} remaining", scale.size(*remaining)),
+    }
+}
+
+fn describe_events(events: &[Event], scale: &DisplayScale) -> String {
+    events.iter().map(|e| describe_event(e, scale)).collect::<Vec<_>>().join("; ")
+}
+
+fn describe_resting(order: &ResolvedOrder, scale: &DisplayScale) -> String {
+    format!("Order {}: {:?} {} @ {}, {} remaining, client_id={:?}, ts={}",
+        order.id, order.side, scale.size(order.quantity), scale.price(order.price), scale.size(order.remaining), order.client_id, order.ts)
+}
+
 fn main() -> Result<()> {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("lobx_rs=info")); 
@@ -29,71 +105,116 @@ fn main() -> Result<()> {
         .compact()
         .init();
 
+    let scale = DisplayScale::from_env();
     let mut book = Book::new();
-    let mut order_history: HashMap<u64, Order> = HashMap::new();
+    let mut order_history: HashMap<OrderId, Order> = HashMap::new();
 
     println!("LOBX demo. Commands:");
     println!("  limit BUY  <price> <qty>");
     println!("  limit SELL <price> <qty>");
     println!("  market BUY  <qty>");
     println!("  market SELL <qty>");
+    println!("  buy|sell <price> <qty>, market_buy|market_sell <qty>");
     println!("  cancel <order_id>");
+    println!("  show <order_id>  (print a resting order)");
     println!("  top    (print best bid/ask)");
+    println!("  stats  (print book metadata)");
     println!("  quit");
-    print_top(&book);
+    print_top(&book, &scale);
 
     let stdin = io::stdin();
     loop {
         print!("> "); let _ = io::stdout().flush();
         let mut line = String::new();
-        if stdin.read_line(&mut line).is_err() { break; }
+        // Ok(0) is end of input (e.g. piped stdin ran out)
+        match stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
         let t: Vec<_> = line.split_whitespace().collect();
         if t.is_empty() { continue; }
         match t[0].to_ascii_lowercase().as_str() {
             "quit" | "q" => break,
-            "top"        => { print_top(&book); }
-            "limit" if t.len()==4 => {
-                if let (Some(side), Ok(px), Ok(q)) =
-                    (parse_side(t[1]), t[2].parse::<i64>(), t[3].parse::<u64>()) {
-                    let req = OrderRequest { side, price: Some(px), quantity: q };
-                    let (order_id, res) = book.submit(&req);
-                    let o = Order { id: order_id, side, price: Some(px), quantity: q };
-                    order_history.insert(order_id, o);
-                    println!("Order ID: {}, events: {:?}", order_id, res.events);
-                    print_top(&book);
-                } else { println!("usage: limit BUY|SELL <price> <qty>"); }
-            }
-            "market" if t.len()==3 => {
-                if let (Some(side), Ok(q)) = (parse_side(t[1]), t[2].parse::<u64>()) {
-                    let req = OrderRequest { side, price: None, quantity: q };
-                    let (order_id, res) = book.submit(&req);
-                    let o = Order { id: order_id, side, price: None, quantity: q };
-                    order_history.insert(order_id, o);
-                    println!("Order ID: {}, events: {:?}", order_id, res.events);
-                    print_top(&book);
-                } else { println!("usage: market BUY|SELL <qty>"); }
+            "top"        => { print_top(&book, &scale); }
+            "stats"      => { println!("{:?}", book.stats()); }
+            "limit" | "market" | "buy" | "sell" | "market_buy" | "market_sell" => {
+                match OrderRequest::parse(&line) {
+                    Ok(req) => {
+                        let (order_id, res) = book.submit(&req);
+                        let o = Order { id: order_id, side: req.side, price: req.price, quantity: req.quantity, client_id: req.client_id };
+                        order_history.insert(order_id, o);
+                        println!("Order ID: {} ({}), events: {}", order_id, describe_order(&req, &scale), describe_events(&res.events, &scale));
+                        print_top(&book, &scale);
+                    }
+                    Err(e) => println!("error: {e}"),
+                }
             }
-            "cancel" if t.len()==2 => {
-                if let Ok(order_id) = t[1].parse::<u64>() {
+            "show" => match parse_order_id(&t) {
+                Ok(order_id) => match book.get_order(order_id) {
+                    Some(order) => println!("{}", describe_resting(&order, &scale)),
+                    None => println!("Order {} is not resting", order_id),
+                },
+                Err(e) => println!("error: {e}"),
+            },
+            "cancel" => match parse_order_id(&t) {
+                Ok(order_id) => {
                     if let Some(original_order) = order_history.get(&order_id).cloned() {
-                        let now = std::time::Instant::now();
-                        let ts = now.elapsed().as_secs();
+                        let ts = book.now();
                         match book.cancel_limit_order(original_order, ts) {
                             Some(result) => {
-                                println!("events: {:?}", result.events);
+                                println!("events: {}", describe_events(&result.events, &scale));
                                 order_history.remove(&order_id);
                             }
                             None => println!("Order {} not found or already cancelled", order_id)
                         }
-                        print_top(&book);
+                        print_top(&book, &scale);
                     } else {
                         println!("Order {} not found in history", order_id);
                     }
-                } else { println!("usage: cancel <order_id>"); }
-            }
-            _ => println!("unknown cmd"),
+                }
+                Err(e) => println!("error: {e}"),
+            },
+            _ => println!("error: {}", ParseError::UnknownCommand(t[0].to_string())),
         }
     }
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lobx_rs::engine::types::{DoneReason, Side};
+
+    #[test]
+    fn test_format_scaled() {
+        let scale = DisplayScale { price: 100, size: 1_000 };
+        assert_eq!(scale.price(123_456), "1234.56");
+        assert_eq!(scale.price(5), "0.05");
+        assert_eq!(scale.price(-250), "-2.50");
+        assert_eq!(scale.size(1_500), "1.500");
+
+        let raw = DisplayScale { price: 1, size: 1 };
+        assert_eq!(raw.price(-7), "-7");
+        assert_eq!(raw.size(42), "42");
+
+        let req = OrderRequest { side: Side::BUY, price: Some(10_050), quantity: 2_000, client_id: None };
+        assert_eq!(describe_order(&req, &scale), "BUY 2.000 @ 100.50");
+        let req = OrderRequest { side: Side::SELL, price: None, quantity: 1, client_id: None };
+        assert_eq!(describe_order(&req, &raw), "SELL 1 @ MKT");
+
+        let events = [
+            Event::Fill { taker_id: OrderId(2), maker_id: OrderId(1), taker_client_id: None, maker_client_id: None, price: 10_050, qty: 500, ts: 0 },
+            Event::Done { id: OrderId(2), client_id: None, reason: DoneReason::Filled, remaining: 0, ts: 0 },
+        ];
+        assert_eq!(describe_events(&events, &scale), "Fill 0.500 @ 100.50 (taker 2, maker 1); Done 2 Filled, 0.000 remaining");
+    }
+
+    #[test]
+    fn test_scale_must_be_power_of_ten() {
+        assert!(is_power_of_ten(1));
+        assert!(is_power_of_ten(1_000_000));
+        assert!(!is_power_of_ten(0));
+        assert!(!is_power_of_ten(500_000));
+    }
+}
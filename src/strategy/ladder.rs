@@ -0,0 +1,46 @@
+use crate::engine::types::{OrderRequest, Side};
+
+// Symmetric quote ladder around a mid price, for strategies that post several
+// levels per side. Level 0 sits `spread_ticks` from the mid; each level after
+// that is `level_step` ticks further out and `size_step` larger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteLadder;
+
+impl QuoteLadder {
+    // `levels` bid/ask pairs, touch first: [bid0, ask0, bid1, ask1, ...]. Prices
+    // aren't clamped, so a wide ladder near zero can produce bids the book rejects.
+    pub fn build(mid: i64, spread_ticks: i64, levels: usize, level_step: i64, base_size: u64, size_step: u64) -> Vec<OrderRequest> {
+        (0..levels).flat_map(|i| {
+            let offset = spread_ticks.saturating_add(level_step.saturating_mul(i as i64));
+            let quantity = base_size.saturating_add(size_step.saturating_mul(i as u64));
+            [
+                OrderRequest { side: Side::BUY, price: Some(mid.saturating_sub(offset)), quantity, client_id: None },
+                OrderRequest { side: Side::SELL, price: Some(mid.saturating_add(offset)), quantity, client_id: None },
+            ]
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(ladder: &[OrderRequest]) -> Vec<(Side, i64, u64)> {
+        ladder.iter().map(|r| (r.side, r.price.unwrap(), r.quantity)).collect()
+    }
+
+    #[test]
+    fn test_ladder_prices_and_sizes() {
+        assert_eq!(summary(&QuoteLadder::build(1_000, 2, 3, 5, 10, 4)), vec![
+            (Side::BUY, 998, 10), (Side::SELL, 1_002, 10),
+            (Side::BUY, 993, 14), (Side::SELL, 1_007, 14),
+            (Side::BUY, 988, 18), (Side::SELL, 1_012, 18),
+        ]);
+        // Flat sizes, one tick apart, joining the mid
+        assert_eq!(summary(&QuoteLadder::build(50, 0, 2, 1, 3, 0)), vec![
+            (Side::BUY, 50, 3), (Side::SELL, 50, 3),
+            (Side::BUY, 49, 3), (Side::SELL, 51, 3),
+        ]);
+        assert!(QuoteLadder::build(50, 1, 0, 1, 3, 0).is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+// End-to-end checks of the demo CLI: drives the real binary over piped stdin.
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+// Feed `input` to the CLI, close stdin and collect its output. Fails instead of
+// hanging if the process doesn't exit on EOF.
+fn run_cli(input: &str) -> Output {
+    run_cli_with_env(input, &[])
+}
+
+fn run_cli_with_env(input: &str, env: &[(&str, &str)]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lobx-rs"))
+        .env("RUST_LOG", "off")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start lobx-rs");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("lobx-rs did not exit at end of input");
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn empty_stdin_exits() {
+    let output = run_cli("");
+    assert!(output.status.success());
+}
+
+#[test]
+fn malformed_input_gets_uniform_errors() {
+    let output = run_cli("\n   \t \nbuy\nbuy abc 5\nlimit HOLD 10 1\nmarket_sell 1 2\ncancel\ncancel 1 2\ncancel xyz\nfrobnicate\nbuy 10 3\n");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    for expected in [
+        "error: 'buy' takes 2 argument(s), got 0",
+        "error: Invalid price 'abc'",
+        "error: Invalid side 'HOLD', expected BUY or SELL",
+        "error: 'market_sell' takes 1 argument(s), got 2",
+        "error: 'cancel' takes 1 argument(s), got 0",
+        "error: 'cancel' takes 1 argument(s), got 2",
+        "error: Invalid order_id 'xyz'",
+        "error: Unknown command 'frobnicate'",
+    ] {
+        assert!(stdout.contains(expected), "missing {expected:?} in:\n{stdout}");
+    }
+    // Still accepting orders after all of that
+    assert!(stdout.contains("Order ID: 1 (BUY 3 @ 10)"), "{stdout}");
+}
+
+#[test]
+fn show_prints_resting_order() {
+    let output = run_cli("sell 20 5\nbuy 20 2\nshow 1\nshow 2\nshow\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Order 1: SELL 5 @ 20, 3 remaining, client_id=None"), "{stdout}");
+    assert!(stdout.contains("Order 2 is not resting"), "{stdout}");
+    assert!(stdout.contains("error: 'show' takes 1 argument(s), got 0"), "{stdout}");
+}
+
+#[test]
+fn event_lines_use_display_scale() {
+    let output = run_cli_with_env("sell 10050 20\nbuy 10050 5\ncancel 1\n", &[("LOBX_PRICE_SCALE", "100"), ("LOBX_SIZE_SCALE", "10")]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Order ID: 1 (SELL 2.0 @ 100.50), events: Done 1 Rested, 2.0 remaining"), "{stdout}");
+    assert!(stdout.contains("Fill 0.5 @ 100.50 (taker 2, maker 1); Done 2 Filled, 0.0 remaining"), "{stdout}");
+    assert!(stdout.contains("events: Done 1 Cancelled, 1.5 remaining"), "{stdout}");
+}